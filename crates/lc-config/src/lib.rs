@@ -17,7 +17,7 @@ mod parsetest;
 
 use lc_examiner::{
     schema::{Answer, Question},
-    settings::{Settings, Test, TestSettings},
+    settings::{Section, Settings, Test, TestSettings},
     Config,
 };
 
@@ -50,7 +50,25 @@ impl TomlConfig {
         let path = root_path.join(&settings.tests_directory_path);
         for test in &settings.tests {
             let test_path = path.join(test.caption.clone() + ".md");
-            let questions = read_test(&test_path);
+            let questions = read_test(&test_path, test)?;
+            if let (Some(open_at), Some(close_at)) = (test.open_at, test.close_at) {
+                if close_at <= open_at {
+                    return Err(format!(
+                        "Тест {} закрывается раньше, чем открывается",
+                        test.caption
+                    )
+                    .into());
+                }
+            }
+            for section in &test.sections {
+                if !questions.sections.iter().any(|s| s.name == section.name) {
+                    return Err(format!(
+                        "В тесте {} нет раздела {}, указанного в настройках",
+                        test.caption, section.name
+                    )
+                    .into());
+                }
+            }
             tests.insert(test.caption.clone(), questions);
 
             test_settings.insert(test.caption.clone(), test.clone());
@@ -139,6 +157,22 @@ impl Config for TomlConfig {
         None
     }
 
+    /// Получить версию теста testname.
+    fn test_version(&self, testname: &String) -> Option<String> {
+        if self.has_test(testname) {
+            return Some(self.tests[testname].version.clone());
+        }
+        None
+    }
+
+    /// Получить разделы теста testname.
+    fn sections(&self, testname: &String) -> Option<Vec<Section>> {
+        if self.has_test(testname) {
+            return Some(self.tests[testname].sections.clone());
+        }
+        None
+    }
+
     /// Получить ответы на вопрос question_id теста testname.
     fn answer(&self, testname: &String, question_id: usize) -> Option<Answer> {
         if self.has_test(testname) && question_id < self.tests[testname].questions.len() {
@@ -235,6 +269,13 @@ mod tests {
             Some(vec!["asd".to_string(), "student".to_string()])
         );
         assert_eq!(settings.number_of_attempts, 3);
+        assert_eq!(settings.pass_percent, Some(50.0));
+        assert_eq!(
+            settings.open_at,
+            chrono::NaiveDate::from_ymd_opt(2025, 9, 1).and_then(|date| date.and_hms_opt(9, 0, 0))
+        );
+        assert_eq!(settings.close_at, None);
+        assert_eq!(settings.grade(72.5), Some("4".to_string()));
     }
 
     #[test]
@@ -253,6 +294,11 @@ mod tests {
                     "Такой команды нет".to_string()
                 ],
                 correct_answer: Answer::new(vec![1]),
+                time_limit_seconds: None,
+                pinned_answers: vec![],
+                answer_order: vec![],
+                weight: 1.0,
+                scoring: None,
             }
         );
     }
@@ -1,39 +1,85 @@
-use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
 use std::path::Path;
 
 use lc_examiner::schema::Answer;
 use lc_examiner::schema::Question;
+use lc_examiner::schema::Scoring;
+use lc_examiner::settings::Section;
+use lc_examiner::settings::SectionSettings;
 use lc_examiner::settings::Test;
 use lc_examiner::settings::TestSettings;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 enum ParseState {
     TestBanner,
+    ReadSection,
     ReadQuestion,
     ReadAnswer,
 }
 
 /// Парсит Markdown файл тестирования
-pub fn read_test(path: &Path) -> Test {
-    let file = File::open(path).expect(format!("Не могу открыть файл теста: {:?}", path).as_str());
-    let file = BufReader::new(file);
+pub fn read_test(path: &Path, settings: &TestSettings) -> Result<Test, String> {
+    let content = std::fs::read(path)
+        .map_err(|err| format!("Не могу открыть файл теста {}: {err}", path.display()))?;
+    let version = test_version(&content, settings);
+    // Вопросы - заголовки самого глубокого уровня, заголовки выше уровнем - разделы.
+    let question_level = content
+        .lines()
+        .filter_map(|line| heading_level(line.unwrap().trim()))
+        .max()
+        .unwrap_or(1);
 
     let mut banner = String::new();
     let mut questions: Vec<Question> = vec![];
+    let mut sections = vec![Section {
+        name: "".to_string(),
+        questions: vec![],
+    }];
     let mut answer_number: usize = 0;
 
     let mut question = Question {
         question: "".to_string(),
         answers: vec![],
         correct_answer: Answer::new(vec![]),
+        time_limit_seconds: None,
+        pinned_answers: vec![],
+        answer_order: vec![],
+        weight: 1.0,
+        scoring: None,
     };
 
     let mut state = ParseState::TestBanner;
 
-    for line in file.lines() {
+    for line in content.lines() {
         let line = line.unwrap().trim().to_string();
 
+        if heading_level(&line).is_some_and(|level| level < question_level) {
+            if matches!(state, ParseState::TestBanner) {
+                banner = banner.trim().to_string();
+            }
+            if !question.question.is_empty() {
+                push_question(&mut questions, &mut sections, question);
+            }
+            question = Question {
+                question: "".to_string(),
+                answers: vec![],
+                correct_answer: Answer::new(vec![]),
+                time_limit_seconds: None,
+                pinned_answers: vec![],
+                answer_order: vec![],
+                weight: 1.0,
+                scoring: None,
+            };
+            answer_number = 0;
+            sections.push(Section {
+                name: line.trim_start_matches('#').trim().to_string(),
+                questions: vec![],
+            });
+            state = ParseState::ReadSection;
+            continue;
+        }
+
         match state {
             ParseState::TestBanner => {
                 if line.starts_with("#") {
@@ -45,6 +91,13 @@ pub fn read_test(path: &Path) -> Test {
                 }
             }
 
+            // Описание раздела не входит в вопросы.
+            ParseState::ReadSection => {
+                if line.starts_with("#") {
+                    state = ParseState::ReadQuestion;
+                }
+            }
+
             ParseState::ReadQuestion => {
                 if line.starts_with("*") || line.starts_with("+") || line.starts_with("-") {
                     question.question = question.question.trim().to_string();
@@ -56,13 +109,18 @@ pub fn read_test(path: &Path) -> Test {
                 if line.starts_with("#") {
                     state = ParseState::ReadQuestion;
                     if question.question.len() > 0 {
-                        questions.push(question);
+                        push_question(&mut questions, &mut sections, question);
                     }
 
                     question = Question {
                         question: "".to_string(),
                         answers: vec![],
                         correct_answer: Answer::new(vec![]),
+                        time_limit_seconds: None,
+                        pinned_answers: vec![],
+                        answer_order: vec![],
+                        weight: 1.0,
+                        scoring: None,
                     };
                     answer_number = 0;
                 }
@@ -71,11 +129,30 @@ pub fn read_test(path: &Path) -> Test {
 
         match state {
             ParseState::ReadQuestion => {
-                question.question += line.to_string().split("#").last().unwrap().trim();
-                question.question += "\n";
+                if let Some(seconds) = time_limit(&line) {
+                    question.time_limit_seconds = Some(seconds);
+                } else if let Some(weight) = weight(&line) {
+                    question.weight = weight;
+                } else if let Some(name) = directive(&line, "scoring") {
+                    let scoring = scoring(name).ok_or_else(|| {
+                        format!(
+                            "Неизвестное правило оценивания {name} в вопросе \"{}\" файла {}",
+                            question.question.trim(),
+                            path.display()
+                        )
+                    })?;
+                    question.scoring = Some(scoring);
+                } else {
+                    question.question += line.to_string().split("#").last().unwrap().trim();
+                    question.question += "\n";
+                }
             }
 
             ParseState::ReadAnswer => {
+                let (line, pin) = match strip_pin(&line) {
+                    Some(answer) => (answer.to_string(), true),
+                    None => (line, false),
+                };
                 if line.starts_with("*") || line.starts_with("-") {
                     // answer
                     question.answers.push(line[1..].trim().to_string());
@@ -93,6 +170,10 @@ pub fn read_test(path: &Path) -> Test {
                         question.answers[i - 1] = answer;
                     }
                 }
+                let last = question.answers.len() - 1;
+                if pin && !question.pinned_answers.contains(&last) {
+                    question.pinned_answers.push(last);
+                }
             }
 
             _ => (),
@@ -100,8 +181,251 @@ pub fn read_test(path: &Path) -> Test {
     }
 
     if question.question.len() > 0 {
-        questions.push(question);
+        push_question(&mut questions, &mut sections, question);
+    }
+    // Вопросы до первого раздела образуют раздел без названия.
+    if sections.len() > 1 && sections[0].questions.is_empty() {
+        sections.remove(0);
     }
 
-    Test { banner, questions }
+    Ok(Test {
+        banner,
+        questions,
+        sections,
+        version,
+    })
+}
+
+/// Правило оценивания по названию: partial, all_or_nothing, no_penalty или negative.
+fn scoring(name: &str) -> Option<Scoring> {
+    toml::Value::String(name.to_string()).try_into().ok()
+}
+
+/// Добавить вопрос в тест и в последний раздел.
+fn push_question(questions: &mut Vec<Question>, sections: &mut [Section], question: Question) {
+    questions.push(question);
+    sections
+        .last_mut()
+        .unwrap()
+        .questions
+        .push(questions.len() - 1);
+}
+
+/// Уровень заголовка Markdown: количество символов # в начале строки.
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    (level > 0).then_some(level)
+}
+
+/// Настройки теста, от которых зависит состав варианта.
+#[derive(Serialize)]
+struct DrawSettings<'a> {
+    questions_number: usize,
+    shuffle_answers: bool,
+    fixed_question_order: bool,
+    shuffle_sections: bool,
+    scoring: Scoring,
+    section: &'a [SectionSettings],
+}
+
+/// Версия теста: первые 16 символов отпечатка SHA-256 файла теста и настроек,
+/// от которых зависит состав варианта. Изменение файла или этих настроек меняет версию.
+fn test_version(content: &[u8], settings: &TestSettings) -> String {
+    let draw = DrawSettings {
+        questions_number: settings.questions_number,
+        shuffle_answers: settings.shuffle_answers,
+        fixed_question_order: settings.fixed_question_order,
+        shuffle_sections: settings.shuffle_sections,
+        scoring: settings.scoring,
+        section: &settings.sections,
+    };
+    let draw = toml::to_string(&draw).unwrap();
+    Sha256::new()
+        .chain_update(content)
+        .chain_update(draw)
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Значение метки вида `<!-- name: value -->` в тексте вопроса.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let value = line
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim()
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix(':')?;
+    Some(value.trim())
+}
+
+/// Время на ответ из строки вида `<!-- time: 30 -->` в тексте вопроса.
+fn time_limit(line: &str) -> Option<u64> {
+    directive(line, "time")?.parse().ok()
+}
+
+/// Вес вопроса из строки вида `<!-- weight: 2.5 -->` в тексте вопроса.
+fn weight(line: &str) -> Option<f32> {
+    directive(line, "weight")?
+        .parse()
+        .ok()
+        .filter(|weight: &f32| *weight >= 0.0)
+}
+
+/// Текст ответа без метки `<!-- pin -->`, если ответ ею отмечен.
+/// Отмеченный ответ не перемешивается с другими.
+fn strip_pin(line: &str) -> Option<&str> {
+    let (answer, directive) = line.rsplit_once("<!--")?;
+    (directive.strip_suffix("-->")?.trim() == "pin").then(|| answer.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn question_directives() {
+        let path = std::env::temp_dir().join("lc_question_directives.md");
+        std::fs::write(
+            &path,
+            "Описание\n\n# 2+2\n<!-- time: 30 -->\n+ 4\n- 5\n\n\
+             # 2*3\n<!-- weight: 2.5 -->\n<!-- scoring: all_or_nothing -->\n\
+             - 5\n+ 6\n- Нет ответа <!-- pin -->\n",
+        )
+        .unwrap();
+
+        let test = read_test(&path, &TestSettings::default()).unwrap();
+        assert_eq!(test.questions[0].question, "2+2");
+        assert_eq!(test.questions[0].time_limit_seconds, Some(30));
+        assert_eq!(test.questions[1].time_limit_seconds, None);
+        assert_eq!(test.questions[0].weight, 1.0);
+        assert_eq!(test.questions[1].weight, 2.5);
+        assert_eq!(test.questions[0].scoring, None);
+        assert_eq!(test.questions[1].scoring, Some(Scoring::AllOrNothing));
+        assert_eq!(test.questions[1].question, "2*3");
+        assert_eq!(test.questions[1].answers[2], "Нет ответа");
+        assert_eq!(test.questions[1].pinned_answers, vec![2]);
+
+        assert_eq!(time_limit("<!--time:5-->"), Some(5));
+        assert_eq!(test.version.len(), 16);
+        assert_eq!(
+            test.version,
+            test_version(&std::fs::read(&path).unwrap(), &TestSettings::default())
+        );
+        assert_eq!(time_limit("<!-- pin -->"), None);
+        assert_eq!(time_limit("<!-- timeout: 5 -->"), None);
+        assert_eq!(weight("<!-- weight: -1 -->"), None);
+        assert_eq!(strip_pin("- Нет ответа <!-- pin -->"), Some("- Нет ответа"));
+        assert_eq!(strip_pin("- 5"), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unknown_scoring() {
+        let path = std::env::temp_dir().join("lc_unknown_scoring.md");
+        std::fs::write(
+            &path,
+            "# 2+2
+<!-- scoring: strict -->
++ 4
+- 5
+",
+        )
+        .unwrap();
+
+        let err = read_test(&path, &TestSettings::default()).unwrap_err();
+        assert!(err.contains("strict"));
+        assert!(err.contains("\"2+2\""));
+        assert!(err.contains("lc_unknown_scoring.md"));
+        assert_eq!(scoring("no_penalty"), Some(Scoring::NoPenalty));
+        let missing = std::env::temp_dir().join("lc_missing_test.md");
+        assert!(read_test(&missing, &TestSettings::default()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_sections() {
+        let path = std::env::temp_dir().join("lc_test_sections.md");
+        std::fs::write(
+            &path,
+            "Описание\n\n## 1+1\n+ 2\n- 3\n\n# Сложение\nОписание раздела\n\n## 2+2\n+ 4\n- 5\n\n\
+             ## 3+3\n+ 6\n- 7\n\n# Умножение\n## 2*3\n- 5\n+ 6\n",
+        )
+        .unwrap();
+
+        let test = read_test(&path, &TestSettings::default()).unwrap();
+        assert_eq!(test.banner, "Описание");
+        let questions: Vec<&str> = test.questions.iter().map(|q| q.question.as_str()).collect();
+        assert_eq!(questions, vec!["1+1", "2+2", "3+3", "2*3"]);
+        let sections: Vec<(&str, Vec<usize>)> = test
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.questions.clone()))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                ("", vec![0]),
+                ("Сложение", vec![1, 2]),
+                ("Умножение", vec![3])
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn version_depends_on_draw_settings() {
+        let content = b"# 2+2\n+ 4\n- 5\n";
+        let settings = TestSettings::default();
+        let version = test_version(content, &settings);
+        assert_eq!(version.len(), 16);
+
+        // Настройки, не влияющие на состав варианта, версию не меняют.
+        let other = TestSettings {
+            test_duration_minutes: 30,
+            number_of_attempts: 2,
+            ..Default::default()
+        };
+        assert_eq!(test_version(content, &other), version);
+
+        let changed = [
+            TestSettings {
+                questions_number: 1,
+                ..Default::default()
+            },
+            TestSettings {
+                shuffle_answers: true,
+                ..Default::default()
+            },
+            TestSettings {
+                fixed_question_order: true,
+                ..Default::default()
+            },
+            TestSettings {
+                shuffle_sections: true,
+                ..Default::default()
+            },
+            TestSettings {
+                scoring: Scoring::AllOrNothing,
+                ..Default::default()
+            },
+            TestSettings {
+                sections: vec![SectionSettings {
+                    name: "Сложение".to_string(),
+                    questions_number: 1,
+                }],
+                ..Default::default()
+            },
+        ];
+        for settings in changed {
+            assert_ne!(test_version(content, &settings), version, "{settings:?}");
+        }
+        assert_ne!(
+            test_version(b"# 2+2\n+ 4\n", &TestSettings::default()),
+            version
+        );
+    }
 }
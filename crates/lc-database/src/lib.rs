@@ -11,6 +11,7 @@ pub mod schema;
 use crate::models::*;
 use crate::schema::*;
 
+use lc_examiner::schema::{AttemptStatus, Mark};
 use lc_examiner::Database;
 
 pub struct TestDatabase {
@@ -48,12 +49,36 @@ impl TestDatabase {
             test_id INTEGER NOT NULL,
             user_id INTEGER NOT NULL,
             mark FLOAT NOT NULL,
+            max_points FLOAT NOT NULL DEFAULT 0,
             start_timestamp VARCHAR NOT NULL,
-            end_timestamp VARCHAR NOT NULL
+            end_timestamp VARCHAR NOT NULL,
+            status VARCHAR NOT NULL DEFAULT 'completed',
+            seed BIGINT,
+            test_version VARCHAR,
+            grade VARCHAR,
+            passed BOOLEAN
         );"#,
         )
         .execute(&mut connection);
 
+        // Базы данных предыдущих версий не содержат состояния попытки.
+        let _ = diesel::sql_query(
+            "ALTER TABLE variants ADD COLUMN status VARCHAR NOT NULL DEFAULT 'completed';",
+        )
+        .execute(&mut connection);
+
+        // Варианты предыдущих версий созданы без сохранения зерна генератора.
+        let _ = diesel::sql_query("ALTER TABLE variants ADD COLUMN seed BIGINT;")
+            .execute(&mut connection);
+        let _ = diesel::sql_query("ALTER TABLE variants ADD COLUMN test_version VARCHAR;")
+            .execute(&mut connection);
+
+        // Оценка по шкале теста и признак сдачи теста.
+        let _ = diesel::sql_query("ALTER TABLE variants ADD COLUMN grade VARCHAR;")
+            .execute(&mut connection);
+        let _ = diesel::sql_query("ALTER TABLE variants ADD COLUMN passed BOOLEAN;")
+            .execute(&mut connection);
+
         let _ = diesel::sql_query(
             r#"
         CREATE TABLE questions (
@@ -71,10 +96,41 @@ impl TestDatabase {
             question_id INTEGER NOT NULL,
             text TEXT NOT NULL,
             is_correct BOOLEAN,
-            is_selected BOOLEAN
+            is_selected BOOLEAN,
+            source_index INTEGER
         );"#,
         )
         .execute(&mut connection);
+
+        // Номер ответа в файле теста, если ответы варианта перемешаны.
+        let _ = diesel::sql_query("ALTER TABLE answers ADD COLUMN source_index INTEGER;")
+            .execute(&mut connection);
+
+        // Незавершенные попытки: вариант хранится целиком в формате JSON.
+        let _ = diesel::sql_query(
+            r#"
+        CREATE TABLE open_variants (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            username VARCHAR NOT NULL,
+            testname VARCHAR NOT NULL,
+            variant TEXT NOT NULL,
+            UNIQUE (username, testname)
+        );"#,
+        )
+        .execute(&mut connection);
+
+        // В предыдущих версиях каждый вопрос стоил один балл.
+        let added = diesel::sql_query(
+            "ALTER TABLE variants ADD COLUMN max_points FLOAT NOT NULL DEFAULT 0;",
+        )
+        .execute(&mut connection);
+        if added.is_ok() {
+            let _ = diesel::sql_query(
+                "UPDATE variants SET max_points = \
+                 (SELECT COUNT(*) FROM questions WHERE questions.variant_id = variants.id);",
+            )
+            .execute(&mut connection);
+        }
         TestDatabase { connection }
     }
 
@@ -125,7 +181,10 @@ impl TestDatabase {
                 .unwrap();
 
             let question_id = added_question.id; 
-            let answers_arr = variant.answers[i].as_array();
+            let answers_arr = variant.answers[i]
+                .as_ref()
+                .map(|answer| answer.as_array())
+                .unwrap_or_default();
             let mut insertable = vec![];
 
             for j in 0..question.answers.len() {
@@ -133,7 +192,8 @@ impl TestDatabase {
                         answers::text.eq(question.answers[j].clone()),
                         answers::question_id.eq(question_id),
                         answers::is_selected.eq(answers_arr.contains(&j)),
-                        answers::is_correct.eq(question.correct_answer.as_array().contains(&j))));
+                        answers::is_correct.eq(question.correct_answer.as_array().contains(&j)),
+                        answers::source_index.eq(question.answer_order.get(j).map(|&i| i as i32))));
             }
                 insert_into(answers::table)
                     .values(&insertable)
@@ -174,6 +234,7 @@ impl TestDatabase {
                     answer: answer.text,
                     is_correct: answer.is_correct,
                     is_selected: answer.is_selected,
+                    source_index: answer.source_index.map(|i| i as usize),
                 })
         }
         let mut result = vec![];
@@ -199,6 +260,7 @@ impl Statistic for TestDatabase {
             .inner_join(users::table)
             .filter(users::name.eq(username))
             .inner_join(tests::table)
+            .order(variants::id)
             .select((Variant::as_select(), User::as_select(), Test::as_select())) //, Test::as_select()))
             .load::<(Variant, User, Test)>(&mut self.connection)
             .unwrap();
@@ -220,8 +282,12 @@ impl Statistic for TestDatabase {
                 username: variant.1.name,
                 testname: variant.2.caption,
                 mark: variant.0.mark,
+                max_points: variant.0.max_points,
+                grade: variant.0.grade,
+                passed: variant.0.passed,
                 end_datetime,
                 start_datetime,
+                status: variant.0.status,
             });
         }
 
@@ -253,13 +319,20 @@ impl Statistic for TestDatabase {
             .unwrap();
 
             let mark = variant.0.mark;
+            let max_points = variant.0.max_points;
             let questions = self.get_questions_records(variant.0.id);
 
             results.push(VariantRecord {
                 mark,
+                max_points,
                 end_datetime,
                 start_datetime,
                 questions,
+                status: variant.0.status,
+                seed: variant.0.seed.map(|seed| seed as u64),
+                test_version: variant.0.test_version,
+                grade: variant.0.grade,
+                passed: variant.0.passed,
             });
         }
         results
@@ -274,22 +347,58 @@ impl Database for TestDatabase {
             .filter(users::name.eq(username))
             .left_join(tests::table)
             .filter(tests::caption.eq(testname))
+            .filter(variants::status.ne(AttemptStatus::Abandoned.as_str()))
             .select(variants::start_timestamp)
             .count()
             .get_result::<i64>(&mut self.connection)
             .unwrap() as u32
     }
 
+    /// Время окончания последней попытки, включая попытки с состоянием Abandoned.
+    /// Попытки, завершенные при остановке сервера, не учитываются.
+    fn last_attempt_end(
+        &mut self,
+        username: &String,
+        testname: &String,
+    ) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let end_timestamp = variants::table
+            .left_join(users::table)
+            .filter(users::name.eq(username))
+            .left_join(tests::table)
+            .filter(tests::caption.eq(testname))
+            .filter(variants::status.ne(AttemptStatus::Interrupted.as_str()))
+            .order(variants::id.desc())
+            .select(variants::end_timestamp)
+            .first::<String>(&mut self.connection)
+            .ok()?;
+        chrono::DateTime::parse_from_str(end_timestamp.as_str(), "%Y-%m-%d %H:%M:%S.%f %z").ok()
+    }
+
     /// Получить баллы за тест testname для пользователя username.
-    fn marks(&mut self, username: &String, testname: &String) -> Vec<f32> {
+    fn marks(&mut self, username: &String, testname: &String) -> Vec<Mark> {
         variants::table
             .left_join(users::table)
             .filter(users::name.eq(username))
             .left_join(tests::table)
             .filter(tests::caption.eq(testname))
-            .select(variants::mark)
-            .load::<f32>(&mut self.connection)
+            .filter(variants::status.ne(AttemptStatus::Abandoned.as_str()))
+            .order(variants::id)
+            .select((
+                variants::mark,
+                variants::max_points,
+                variants::grade,
+                variants::passed,
+            ))
+            .load::<(f32, f32, Option<String>, Option<bool>)>(&mut self.connection)
             .unwrap()
+            .into_iter()
+            .map(|(points, max_points, grade, passed)| Mark {
+                points,
+                max_points,
+                grade,
+                passed,
+            })
+            .collect()
     }
 
     /// Сохранить баллы за тест testname для пользователя username.
@@ -297,10 +406,11 @@ impl Database for TestDatabase {
         &mut self,
         username: &String,
         testname: &String,
-        mark_value: f32,
+        mark: Mark,
         start_time: &String,
         end_time: &String,
         variant: &lc_examiner::schema::Variant,
+        status: AttemptStatus,
     ) {
         let user_id_f = self.append_user(username.clone());
         let test_id_f = self.append_test(testname.clone());
@@ -316,9 +426,16 @@ impl Database for TestDatabase {
                 .values((
                     variants::user_id.eq(user_id_f),
                     variants::test_id.eq(test_id_f),
-                    variants::mark.eq(mark_value),
+                    variants::mark.eq(mark.points),
+                    variants::max_points.eq(mark.max_points),
                     variants::start_timestamp.eq(start_time.clone()),
                     variants::end_timestamp.eq(end_time.clone()),
+                    variants::status.eq(status.as_str()),
+                    // SQLite хранит 64-битные целые со знаком, зерно сохраняется побитово.
+                    variants::seed.eq(variant.seed.map(|seed| seed as i64)),
+                    variants::test_version.eq(&variant.test_version),
+                    variants::grade.eq(&mark.grade),
+                    variants::passed.eq(mark.passed),
                 ))
                 .execute(&mut self.connection)
                 .unwrap();
@@ -334,6 +451,65 @@ impl Database for TestDatabase {
 
         self.append_questions(mark_id, variant);
     }
+
+    /// Сохранить незавершенную попытку, заменив ранее сохраненную.
+    fn save_open_variant(&mut self, variant: &lc_examiner::schema::Variant) {
+        let json = match serde_json::to_string(variant) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("Не удалось сохранить попытку {}: {err}", variant.username);
+                return;
+            }
+        };
+
+        let result = insert_into(open_variants::table)
+            .values((
+                open_variants::username.eq(&variant.username),
+                open_variants::testname.eq(&variant.testname),
+                open_variants::variant.eq(&json),
+            ))
+            .on_conflict((open_variants::username, open_variants::testname))
+            .do_update()
+            .set(open_variants::variant.eq(&json))
+            .execute(&mut self.connection);
+        if let Err(err) = result {
+            error!("Не удалось сохранить попытку {}: {err}", variant.username);
+        }
+    }
+
+    /// Удалить сохраненную незавершенную попытку.
+    fn remove_open_variant(&mut self, username: &String, testname: &String) {
+        let result = diesel::delete(
+            open_variants::table
+                .filter(open_variants::username.eq(username))
+                .filter(open_variants::testname.eq(testname)),
+        )
+        .execute(&mut self.connection);
+        if let Err(err) = result {
+            error!("Не удалось удалить попытку {username}: {err}");
+        }
+    }
+
+    /// Загрузить сохраненные незавершенные попытки.
+    fn load_open_variants(&mut self) -> Vec<lc_examiner::schema::Variant> {
+        let rows = open_variants::table
+            .select(open_variants::variant)
+            .load::<String>(&mut self.connection)
+            .unwrap_or_else(|err| {
+                error!("Не удалось загрузить незавершенные попытки: {err}");
+                vec![]
+            });
+
+        rows.iter()
+            .filter_map(|json| match serde_json::from_str(json) {
+                Ok(variant) => Some(variant),
+                Err(err) => {
+                    error!("Не удалось восстановить попытку: {err}");
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -354,31 +530,21 @@ mod db_tests {
         db.append_mark(
             &"vlad".to_string(),
             &"math".to_string(),
-            5.0,
+            Mark::new(5.0, 10.0),
             &start_time,
             &end_time,
-            &lc_examiner::schema::Variant {
-                username: "vlad".to_string(),
-                testname: "math".to_string(),
-                start_timestamp: chrono::offset::Local::now(),
-                questions: vec![],
-                answers: vec![],
-            },
+            &lc_examiner::schema::Variant::new("vlad", "math"),
+            AttemptStatus::Completed,
         );
 
         db.append_mark(
             &"sveta".to_string(),
             &"math".to_string(),
-            8.8,
+            Mark::new(8.8, 10.0),
             &start_time,
             &end_time,
-            &lc_examiner::schema::Variant {
-                username: "sveta".to_string(),
-                testname: "math".to_string(),
-                start_timestamp: chrono::offset::Local::now(),
-                questions: vec![],
-                answers: vec![],
-            },
+            &lc_examiner::schema::Variant::new("sveta", "math"),
+            AttemptStatus::Completed,
         );
         let start_datetime = "2025-01-26 13:33:41.789001340 +03:00".to_string();
 
@@ -386,32 +552,26 @@ mod db_tests {
         db.append_mark(
             &"artem".to_string(),
             &"history".to_string(),
-            4.83,
+            Mark {
+                grade: Some("3".to_string()),
+                passed: Some(false),
+                ..Mark::new(4.83, 10.0)
+            },
             &start_datetime,
             &end_datetime,
-            &lc_examiner::schema::Variant {
-                username: "artem".to_string(),
-                testname: "history".to_string(),
-                start_timestamp: chrono::offset::Local::now(),
-                questions: vec![],
-                answers: vec![],
-            },
+            &lc_examiner::schema::Variant::new("artem", "history"),
+            AttemptStatus::Completed,
         );
         let start_time = "5".to_string();
         let end_time = "6".to_string();
         db.append_mark(
             &"vlad".to_string(),
             &"math".to_string(),
-            3.2,
+            Mark::new(3.2, 10.0),
             &start_time,
             &end_time,
-            &lc_examiner::schema::Variant {
-                username: "vlad".to_string(),
-                testname: "math".to_string(),
-                start_timestamp: chrono::offset::Local::now(),
-                questions: vec![],
-                answers: vec![],
-            },
+            &lc_examiner::schema::Variant::new("vlad", "math"),
+            AttemptStatus::Completed,
         );
     }
 
@@ -422,14 +582,26 @@ mod db_tests {
 
         fill_database(&mut db);
 
-        assert_eq!(db.marks(&"artem".to_string(), &"math".to_string()), vec![]);
+        assert_eq!(
+            db.marks(&"artem".to_string(), &"math".to_string()),
+            vec![] as Vec<Mark>
+        );
+        let mark = |points| Mark::new(points, 10.0);
         assert_eq!(
             db.marks(&"vlad".to_string(), &"math".to_string()),
-            vec![5.0, 3.2]
+            vec![mark(5.0), mark(3.2)]
         );
         assert_eq!(
             db.marks(&"sveta".to_string(), &"math".to_string()),
-            vec![8.8]
+            vec![mark(8.8)]
+        );
+        assert_eq!(
+            db.marks(&"artem".to_string(), &"history".to_string()),
+            vec![Mark {
+                grade: Some("3".to_string()),
+                passed: Some(false),
+                ..mark(4.83)
+            }]
         );
 
         std::fs::remove_file(db_path).unwrap();
@@ -482,6 +654,42 @@ mod db_tests {
         std::fs::remove_file(db_path).unwrap();
     }
 
+    #[test]
+    fn open_variants() {
+        let db_path = "/tmp/lc_open_variants.db";
+        let mut db = TestDatabase::new(db_path.to_string());
+        assert!(db.load_open_variants().is_empty());
+
+        let mut variant = lc_examiner::schema::Variant {
+            questions: vec![lc_examiner::schema::Question {
+                question: "2+2".to_string(),
+                answers: vec!["4".to_string(), "5".to_string()],
+                correct_answer: lc_examiner::schema::Answer::new(vec![0]),
+                time_limit_seconds: None,
+                pinned_answers: vec![],
+                answer_order: vec![],
+                weight: 1.0,
+                scoring: None,
+            }],
+            ..lc_examiner::schema::Variant::new("vlad", "math")
+        };
+        db.save_open_variant(&variant);
+        variant
+            .answers
+            .push(Some(lc_examiner::schema::Answer::new(vec![1])));
+        db.save_open_variant(&variant);
+        assert_eq!(db.load_open_variants(), vec![variant.clone()]);
+
+        // Сохраненная попытка переживает повторное открытие базы данных.
+        let mut db = TestDatabase::new(db_path.to_string());
+        assert_eq!(db.load_open_variants(), vec![variant]);
+
+        db.remove_open_variant(&"vlad".to_string(), &"math".to_string());
+        assert!(db.load_open_variants().is_empty());
+
+        std::fs::remove_file(db_path).unwrap();
+    }
+
     #[test]
     fn statistic() {
         let db_path = "/tmp/lc_statistic.db";
@@ -506,11 +714,200 @@ mod db_tests {
             username: "artem".to_string(),
             testname: "history".to_string(),
             mark: 4.83,
+            max_points: 10.0,
+            grade: Some("3".to_string()),
+            passed: Some(false),
             start_datetime,
             end_datetime,
+            status: "completed".to_string(),
         }];
         assert_eq!(res, expected);
 
         std::fs::remove_file(db_path).unwrap();
     }
+
+    #[test]
+    fn abandoned_attempts() {
+        let db_path = "/tmp/lc_abandoned_attempts.db";
+        let mut db = TestDatabase::new(db_path.to_string());
+        fill_database(&mut db);
+
+        let start_time = "2025-01-27 10:00:00.000000000 +03:00".to_string();
+        let end_time = "2025-01-27 10:05:00.000000000 +03:00".to_string();
+        let variant = lc_examiner::schema::Variant::new("sveta", "history");
+        db.append_mark(
+            &"sveta".to_string(),
+            &"history".to_string(),
+            Mark::new(1.0, 10.0),
+            &start_time,
+            &end_time,
+            &variant,
+            AttemptStatus::Abandoned,
+        );
+
+        // Попытка сохраняется, но не расходует попытки и не дает оценки.
+        assert_eq!(
+            db.attempts_counter(&"sveta".to_string(), &"history".to_string()),
+            0
+        );
+        assert_eq!(
+            db.marks(&"sveta".to_string(), &"history".to_string()),
+            vec![] as Vec<Mark>
+        );
+        let variants = db.variants(&"sveta".to_string(), &"history".to_string());
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].status, "abandoned");
+
+        // Попытка, от которой отказались, учитывается при ограничении частоты попыток.
+        assert_eq!(
+            db.last_attempt_end(&"sveta".to_string(), &"history".to_string()),
+            chrono::DateTime::parse_from_str(&end_time, "%Y-%m-%d %H:%M:%S.%f %z").ok()
+        );
+        assert_eq!(
+            db.last_attempt_end(&"sveta".to_string(), &"physics".to_string()),
+            None
+        );
+
+        std::fs::remove_file(db_path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_attempts() {
+        let db_path = "/tmp/lc_interrupted_attempts.db";
+        let mut db = TestDatabase::new(db_path.to_string());
+        fill_database(&mut db);
+
+        let start_time = "2025-01-27 10:00:00.000000000 +03:00".to_string();
+        let end_time = "2025-01-27 10:05:00.000000000 +03:00".to_string();
+        let variant = lc_examiner::schema::Variant::new("sveta", "history");
+        db.append_mark(
+            &"sveta".to_string(),
+            &"history".to_string(),
+            Mark::new(4.0, 10.0),
+            &start_time,
+            &end_time,
+            &variant,
+            AttemptStatus::Interrupted,
+        );
+
+        // Попытка, прерванная остановкой сервера, оценивается как обычная.
+        assert_eq!(
+            db.attempts_counter(&"sveta".to_string(), &"history".to_string()),
+            1
+        );
+        assert_eq!(
+            db.marks(&"sveta".to_string(), &"history".to_string()),
+            vec![Mark::new(4.0, 10.0)]
+        );
+        let variants = db.variants(&"sveta".to_string(), &"history".to_string());
+        assert_eq!(variants[0].status, "interrupted");
+
+        // Но не учитывается при ограничении частоты попыток.
+        assert_eq!(
+            db.last_attempt_end(&"sveta".to_string(), &"history".to_string()),
+            None
+        );
+
+        std::fs::remove_file(db_path).unwrap();
+    }
+
+    #[test]
+    fn variant_generation() {
+        let db_path = "/tmp/lc_variant_generation.db";
+        let mut db = TestDatabase::new(db_path.to_string());
+        fill_database(&mut db);
+
+        let start_time = "2025-01-27 10:00:00.000000000 +03:00".to_string();
+        let end_time = "2025-01-27 10:05:00.000000000 +03:00".to_string();
+        let variant = lc_examiner::schema::Variant {
+            // Ответы перемешаны: правильный ответ "4" показан вторым.
+            questions: vec![lc_examiner::schema::Question {
+                question: "2+2".to_string(),
+                answers: vec!["5".to_string(), "4".to_string()],
+                correct_answer: lc_examiner::schema::Answer::new(vec![1]),
+                time_limit_seconds: None,
+                pinned_answers: vec![],
+                answer_order: vec![1, 0],
+                weight: 1.0,
+                scoring: None,
+            }],
+            answers: vec![Some(lc_examiner::schema::Answer::new(vec![1]))],
+            seed: Some(u64::MAX),
+            test_version: Some("0123456789abcdef".to_string()),
+            ..lc_examiner::schema::Variant::new("sveta", "history")
+        };
+        db.append_mark(
+            &"sveta".to_string(),
+            &"history".to_string(),
+            Mark::new(1.0, 10.0),
+            &start_time,
+            &end_time,
+            &variant,
+            AttemptStatus::Completed,
+        );
+
+        let variants = db.variants(&"sveta".to_string(), &"history".to_string());
+        assert_eq!(variants[0].seed, Some(u64::MAX));
+        assert_eq!(variants[0].test_version, variant.test_version);
+        let answers = &variants[0].questions[0].answers;
+        assert_eq!(answers[0].source_index, Some(1));
+        assert_eq!(answers[1].source_index, Some(0));
+        assert!(answers[1].is_correct && answers[1].is_selected);
+        // Варианты без сохраненного зерна.
+        let variants = db.variants(&"artem".to_string(), &"history".to_string());
+        assert_eq!(variants[0].seed, None);
+
+        std::fs::remove_file(db_path).unwrap();
+    }
+
+    #[test]
+    fn status_column_migration() {
+        let db_path = "/tmp/lc_status_migration.db";
+        let mut connection = SqliteConnection::establish(db_path).unwrap();
+        diesel::sql_query(
+            r#"CREATE TABLE variants (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            test_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            mark FLOAT NOT NULL,
+            start_timestamp VARCHAR NOT NULL,
+            end_timestamp VARCHAR NOT NULL
+            );"#,
+        )
+        .execute(&mut connection)
+        .unwrap();
+        diesel::sql_query(
+            r#"CREATE TABLE questions (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            variant_id NOT NULL,
+            text TEXT NOT NULL
+            );"#,
+        )
+        .execute(&mut connection)
+        .unwrap();
+        diesel::sql_query("INSERT INTO variants VALUES (1, 99, 99, 1.5, '1', '2');")
+            .execute(&mut connection)
+            .unwrap();
+        diesel::sql_query("INSERT INTO questions VALUES (1, 1, '2+2'), (2, 1, '2*3');")
+            .execute(&mut connection)
+            .unwrap();
+        drop(connection);
+
+        // Попытки из базы предыдущей версии считаются завершенными.
+        let mut db = TestDatabase::new(db_path.to_string());
+        fill_database(&mut db);
+        assert_eq!(
+            db.attempts_counter(&"vlad".to_string(), &"math".to_string()),
+            2
+        );
+        // Максимальный балл старых попыток - количество вопросов варианта.
+        let max_points = variants::table
+            .filter(variants::id.eq(1))
+            .select(variants::max_points)
+            .get_result::<f32>(&mut db.connection)
+            .unwrap();
+        assert_eq!(max_points, 2.0);
+
+        std::fs::remove_file(db_path).unwrap();
+    }
 }
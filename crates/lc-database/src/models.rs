@@ -26,8 +26,14 @@ pub struct Variant {
     pub test_id: i32,
     pub user_id: i32,
     pub mark: f32,
+    pub max_points: f32,
     pub start_timestamp: String,
     pub end_timestamp: String,
+    pub status: String,
+    pub seed: Option<i64>,
+    pub test_version: Option<String>,
+    pub grade: Option<String>,
+    pub passed: Option<bool>,
 }
 
 #[derive(Queryable, Selectable, Insertable, Associations, Identifiable, Debug, PartialEq)]
@@ -50,4 +56,5 @@ pub struct Answer {
     pub text: String,
     pub is_correct: bool,
     pub is_selected: bool,
+    pub source_index: Option<i32>,
 }
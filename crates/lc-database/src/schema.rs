@@ -20,8 +20,14 @@ diesel::table! {
         test_id -> Integer,
         user_id -> Integer,
         mark -> Float,
+        max_points -> Float,
         start_timestamp -> Text,
         end_timestamp -> Text,
+        status -> Text,
+        seed -> Nullable<BigInt>,
+        test_version -> Nullable<Text>,
+        grade -> Nullable<Text>,
+        passed -> Nullable<Bool>,
     }
 }
 
@@ -40,6 +46,16 @@ diesel::table! {
         text -> Text,
         is_correct -> Bool,
         is_selected -> Bool,
+        source_index -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    open_variants (id) {
+        id -> Integer,
+        username -> Text,
+        testname -> Text,
+        variant -> Text,
     }
 }
 
@@ -48,4 +64,11 @@ diesel::joinable!(variants -> tests (test_id));
 diesel::joinable!(questions -> variants (variant_id));
 diesel::joinable!(answers -> questions (question_id));
 
-diesel::allow_tables_to_appear_in_same_query!(tests, users, variants, questions, answers);
+diesel::allow_tables_to_appear_in_same_query!(
+    tests,
+    users,
+    variants,
+    questions,
+    answers,
+    open_variants
+);
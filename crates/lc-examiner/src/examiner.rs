@@ -1,29 +1,92 @@
 use std::collections::HashMap;
 
-use log::{debug, error};
+use log::{debug, error, info};
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 use crate::network::Marks;
-use crate::schema::{Answer, Variant};
+use crate::schema::{Answer, AttemptStatus, Availability, Mark, Scoring, Variant, WindowState};
+use crate::settings::{Section, Settings, ShutdownPolicy, TestSettings};
 use crate::{network::Response, schema::Question};
 use crate::{Config, Database};
 
+/// Запас времени на передачу ответа по сети после окончания времени на вопрос.
+const QUESTION_GRACE_SECONDS: i64 = 2;
+
+/// Время хранения токенов завершенных попыток, в минутах.
+const CLOSED_SESSION_GRACE_MINUTES: i64 = 60;
+
+/// Причина, по которой пользователь не может начать попытку.
+enum AttemptDenied {
+    /// Попытки закончились.
+    NoAttempts,
+    /// Не истек перерыв после предыдущей попытки.
+    Cooldown { remaining_seconds: u64 },
+}
+
 pub struct Examiner {
     config: Box<dyn Config>,
     db: Box<dyn Database>,
-    /// Хранилище вариантов - username - variants
-    variants: HashMap<String, Variant>,
+    /// Хранилище вариантов - (username, testname) - variant
+    variants: HashMap<(String, String), Variant>,
+    /// Открытые сессии - token - (username, testname)
+    sessions: HashMap<String, (String, String)>,
+    /// Токены открытых сессий - (username, testname) - token
+    session_tokens: HashMap<(String, String), String>,
+    /// Сессии завершенных попыток - token - ((username, testname), время закрытия)
+    closed_sessions: HashMap<String, ((String, String), chrono::DateTime<chrono::Local>)>,
 }
 
 impl Examiner {
-    pub fn new(config: Box<dyn Config>, db: Box<dyn Database>) -> Examiner {
-        let examiner = Examiner {
+    pub fn new(config: Box<dyn Config>, mut db: Box<dyn Database>) -> Examiner {
+        // Попытки, сохраненные до остановки или аварийного завершения сервера,
+        // продолжаются с прежним временем начала.
+        let mut variants = HashMap::new();
+        for variant in db.load_open_variants() {
+            info!(
+                "Восстановлена попытка пользователя {} по тесту {}.",
+                variant.username, variant.testname
+            );
+            variants.insert(
+                (variant.username.clone(), variant.testname.clone()),
+                variant,
+            );
+        }
+
+        Examiner {
             config,
             db,
-            variants: HashMap::new(),
-        };
-        examiner
+            variants,
+            sessions: HashMap::new(),
+            session_tokens: HashMap::new(),
+            closed_sessions: HashMap::new(),
+        }
+    }
+
+    /// Обработать незавершенные попытки перед остановкой сервера.
+    pub fn shutdown(&mut self, policy: ShutdownPolicy) {
+        let open: Vec<(String, String)> = self.variants.keys().cloned().collect();
+
+        match policy {
+            ShutdownPolicy::Finish => {
+                info!("Завершаю незавершенные попытки: {}.", open.len());
+                for (username, testname) in open {
+                    self.done_test(&username, &testname, AttemptStatus::Interrupted);
+                }
+            }
+            ShutdownPolicy::Checkpoint => {
+                info!("Сохраняю незавершенные попытки: {}.", open.len());
+                for variant in self.variants.values() {
+                    self.db.save_open_variant(variant);
+                }
+            }
+        }
+    }
+
+    /// Параметры сервера.
+    pub fn settings(&self) -> Settings {
+        self.config.settings()
     }
 
     /// Показать описание теста перед запуском
@@ -36,19 +99,25 @@ impl Examiner {
             return Response::NotAllowedUser;
         }
 
+        // Тест может быть еще не открыт или уже закрыт.
+        if let Some(response) = self.check_window(username, testname) {
+            return response;
+        }
+
         // Или закончатся попытки.
-        if !self.has_attempt(username, testname) {
-            debug!(
-                "У пользователя {username} больше не осталось попыток на прохождение {testname}."
-            );
-            return Response::End {
-                marks: self.get_marks(username, testname),
-            };
+        if let Err(denied) = self.has_attempt(username, testname) {
+            return self.attempt_denied(username, testname, denied);
+        }
+
+        // Или у пользователя уже идет другой тест.
+        if let Some(response) = self.check_parallel_tests(username, testname) {
+            return response;
         }
 
         // Отправить описание теста.
         Response::TestStarted {
             banner: self.config.test_banner(testname).unwrap_or("".to_string()),
+            session: self.open_session(username, testname),
         }
     }
 
@@ -59,8 +128,10 @@ impl Examiner {
             return Marks::Empty;
         }
 
-        if self.config.test_settings(testname).unwrap().show_results {
-            Marks::Marks { marks }
+        let settings = self.config.test_settings(testname).unwrap();
+        if settings.show_results {
+            let official = settings.official_mark(&marks);
+            Marks::Marks { marks, official }
         } else {
             Marks::Done
         }
@@ -75,180 +146,581 @@ impl Examiner {
             let user_tests = self.config.user_tests_list(username);
             let mut tests = vec![];
             for test in &user_tests {
-                tests.push((test.clone(), self.get_marks(username, test)));
+                let marks = self.get_marks(username, test);
+                tests.push((test.clone(), marks, self.availability(test)));
             }
             Response::AvaliableTests { tests }
         }
     }
 
-    /// Сохранить ответ на вопрос, отправить следующий вопрос или оценку.
+    /// Сохранить ответ на последний выданный вопрос, отправить следующий вопрос или оценку.
+    /// Ответ после окончания времени на вопрос не сохраняется.
     pub fn put_answer(
         &mut self,
         username: &String,
         testname: &String,
         answer: &Answer,
+        session: &Option<String>,
     ) -> Response {
-        // У пользователя может не быть доступа.
-        if !self.config.has_access(username, testname) {
-            error!(
-                "Пользователь {username} пытался ответить на вопрос теста {testname} не имея не это прав."
-            );
-            return Response::NotAllowedUser;
+        if let Some(response) = self.check_attempt(username, testname, session) {
+            return response;
         }
-        // Тест может быть не запущен
-        if !self.is_user_have_opened_variant(username, testname) {
-            error!("Тест завершен, нельзя отвечать на вопросы: {username}, {testname} {answer:?}");
-            return Response::End {
-                marks: self.get_marks(username, testname),
-            };
+        if let Some(index) = self.current_question(username, testname) {
+            self.set_answer_on_question(username, testname, index, answer);
         }
-        self.push_answer_on_current_question(username, &answer);
-        self.next_question(username, testname)
+        self.next_question(username, testname, session)
     }
 
-    /// Запустить тест или отправить новый вопрос.
-    pub fn next_question(&mut self, username: &String, testname: &String) -> Response {
-        // У пользователя может не быть доступа.
-        if !self.config.has_access(username, testname) {
+    /// Запустить тест или отправить первый неотвеченный вопрос, время на который не истекло.
+    /// Если таких вопросов не осталось, тест завершается.
+    pub fn next_question(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.open_attempt(username, testname, session) {
+            return response;
+        }
+
+        // Если есть неотвеченные вопросы.
+        match self.first_unanswered(username, testname) {
+            Some(index) => self.question_response(username, testname, index),
+            None => self.finish(username, testname, AttemptStatus::Completed),
+        }
+    }
+
+    /// Запустить тест или отправить вопрос с номером index.
+    pub fn get_question(
+        &mut self,
+        username: &String,
+        testname: &String,
+        index: usize,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.open_attempt(username, testname, session) {
+            return response;
+        }
+        if !self.has_question(username, testname, index) {
             error!(
-                "Пользователь {username} пытался получить следующий вопрос {testname} не имея доступа к тесту."
+                "Пользователь {username} запросил несуществующий вопрос {index} теста {testname}."
             );
-            return Response::NotAllowedUser;
+            return Response::ResponseError;
         }
+        self.question_response(username, testname, index)
+    }
 
-        // Или закончатся попытки.
-        if !self.has_attempt(username, testname) {
-            debug!(
-                "У пользователя {username} больше не осталось попыток на прохождение {testname}."
-            );
-            return Response::End {
-                marks: self.get_marks(username, testname),
-            };
+    /// Сохранить или заменить ответ на вопрос с номером index.
+    /// Пустой ответ отменяет ранее данный ответ.
+    pub fn set_answer(
+        &mut self,
+        username: &String,
+        testname: &String,
+        index: usize,
+        answer: &Answer,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.check_attempt(username, testname, session) {
+            return response;
         }
+        if !self.has_question(username, testname, index) {
+            error!("Пользователь {username} ответил на несуществующий вопрос {index} теста {testname}.");
+            return Response::ResponseError;
+        }
+        if self.set_answer_on_question(username, testname, index, answer) {
+            Response::Ok
+        } else {
+            Response::QuestionTimeOver
+        }
+    }
 
-        // Если пользователь ещё не начал тестирование.
-        if !self.is_user_have_opened_variant(username, testname) {
-            self.start_test(username, testname);
+    /// Запустить тест или отправить перечень отвеченных вопросов.
+    pub fn answers_state(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.open_attempt(username, testname, session) {
+            return response;
+        }
+        let variant = &self.variants[&key(username, testname)];
+        Response::AnswersState {
+            answered: variant.answers.iter().map(Option::is_some).collect(),
+            closed: (0..variant.questions.len())
+                .map(|index| self.is_question_closed(variant, index))
+                .collect(),
+            remaining_seconds: self.remaining_seconds(variant),
         }
+    }
 
-        // Если есть неотвеченные вопросы.
-        if self.is_next_question(username) {
-            self.get_next_question(username)
-        } else {
-            self.done_test(username, testname);
-            Response::End {
-                marks: self.get_marks(username, testname),
-            }
+    /// Завершить тест по запросу пользователя, вопросы без ответа оцениваются нулем.
+    pub fn finish_test(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.check_attempt(username, testname, session) {
+            return response;
+        }
+        self.finish(username, testname, AttemptStatus::Finished)
+    }
+
+    /// Отказаться от попытки, если это разрешено настройками теста.
+    /// Попытка сохраняется, но не учитывается в оценках и количестве попыток.
+    pub fn abandon_test(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Response {
+        if let Some(response) = self.check_attempt(username, testname, session) {
+            return response;
         }
+        if !self.config.test_settings(testname).unwrap().allow_abandon {
+            debug!("Пользователю {username} запрещено отказаться от попытки теста {testname}.");
+            return Response::AbandonNotAllowed;
+        }
+        debug!("Пользователь {username} отказался от попытки теста {testname}.");
+        self.finish(username, testname, AttemptStatus::Abandoned)
     }
 
     pub fn variant_collector(&mut self) {
         let mut done_tests = vec![];
 
-        for it in &self.variants {
-            let variant = it.1;
-            if self.is_test_time_is_over(&variant.username, &variant.testname) {
-                done_tests.push((variant.username.clone(), variant.testname.clone()));
+        for (username, testname) in self.variants.keys() {
+            if self.is_test_time_is_over(username, testname) {
+                done_tests.push((username.clone(), testname.clone()));
             }
         }
 
         for variant in done_tests {
-            self.done_test(&variant.0, &variant.1);
+            self.done_test(&variant.0, &variant.1, AttemptStatus::Expired);
         }
+
+        let expired =
+            chrono::Local::now() - chrono::Duration::minutes(CLOSED_SESSION_GRACE_MINUTES);
+        self.closed_sessions
+            .retain(|_, (_, closed_at)| *closed_at > expired);
     }
 
     /// Проверка наличия попыток у пользователя.
-    fn has_attempt(&mut self, username: &String, testname: &String) -> bool {
-        let number_of_attempts = match self.config.test_settings(testname) {
-            Some(conf) => conf.number_of_attempts,
+    /// Возвращает причину, если начать попытку нельзя.
+    fn has_attempt(&mut self, username: &String, testname: &String) -> Result<(), AttemptDenied> {
+        let (number_of_attempts, cooldown_minutes) = match self.config.test_settings(testname) {
+            Some(conf) => (conf.number_of_attempts, conf.retry_cooldown_minutes),
             None => {
                 error!("Тест {testname} требуемый пользователем {username} не обнаружен");
-                0
+                (0, 0)
             }
         };
-        number_of_attempts <= 0 || self.db.attempts_counter(username, testname) < number_of_attempts
+
+        if number_of_attempts > 0
+            && self.db.attempts_counter(username, testname) >= number_of_attempts
+        {
+            debug!(
+                "У пользователя {username} больше не осталось попыток на прохождение {testname}."
+            );
+            return Err(AttemptDenied::NoAttempts);
+        }
+
+        // Начатую попытку можно продолжать, ограничение касается только новых попыток.
+        if cooldown_minutes > 0 && !self.is_user_have_opened_variant(username, testname) {
+            if let Some(end) = self.db.last_attempt_end(username, testname) {
+                let available = end + chrono::Duration::minutes(cooldown_minutes);
+                let remaining = available.timestamp() - chrono::Local::now().timestamp();
+                if remaining > 0 {
+                    debug!(
+                        "Пользователь {username} сможет начать тест {testname} через {remaining} с."
+                    );
+                    return Err(AttemptDenied::Cooldown {
+                        remaining_seconds: remaining as u64,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ответ на запрос попытки, которую нельзя начать.
+    fn attempt_denied(
+        &mut self,
+        username: &String,
+        testname: &String,
+        denied: AttemptDenied,
+    ) -> Response {
+        match denied {
+            AttemptDenied::NoAttempts => Response::End {
+                marks: self.get_marks(username, testname),
+            },
+            AttemptDenied::Cooldown { remaining_seconds } => {
+                Response::AttemptCooldown { remaining_seconds }
+            }
+        }
+    }
+
+    /// Проверить доступ к тесту и сессию, запустить тест, если он ещё не начат.
+    /// Возвращает ответ на запрос, который нельзя выполнить.
+    fn open_attempt(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Option<Response> {
+        // У пользователя может не быть доступа.
+        if !self.config.has_access(username, testname) {
+            error!("Пользователь {username} обратился к тесту {testname} не имея доступа к нему.");
+            return Some(Response::NotAllowedUser);
+        }
+
+        // Запрос должен относиться к выданной сессии.
+        if let Some(response) = self.check_session(username, testname, session) {
+            return Some(response);
+        }
+
+        // Или закончатся попытки.
+        if let Err(denied) = self.has_attempt(username, testname) {
+            return Some(self.attempt_denied(username, testname, denied));
+        }
+
+        // Если пользователь ещё не начал тестирование.
+        if !self.is_user_have_opened_variant(username, testname) {
+            if let Some(response) = self.check_window(username, testname) {
+                return Some(response);
+            }
+            if let Some(response) = self.check_parallel_tests(username, testname) {
+                return Some(response);
+            }
+            self.start_test(username, testname);
+        } else if self.is_test_time_is_over(username, testname) {
+            debug!("Время теста {testname} пользователя {username} истекло.");
+            return Some(self.finish(username, testname, AttemptStatus::Expired));
+        }
+        None
+    }
+
+    /// Окно доступности теста testname в текущий момент.
+    fn availability(&self, testname: &String) -> Availability {
+        self.config
+            .test_settings(testname)
+            .unwrap_or_default()
+            .availability(chrono::Local::now().naive_local())
+    }
+
+    /// Запретить начало попытки вне окна доступности теста.
+    fn check_window(&self, username: &String, testname: &String) -> Option<Response> {
+        let availability = self.availability(testname);
+        if availability.state == WindowState::Open {
+            return None;
+        }
+        debug!(
+            "Пользователь {username} пытался начать тест {testname} вне окна доступности: {availability:?}."
+        );
+        Some(Response::TestUnavailable { availability })
+    }
+
+    /// Проверить доступ к тесту, сессию и наличие начатой попытки.
+    /// Возвращает ответ на запрос, который нельзя выполнить.
+    fn check_attempt(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Option<Response> {
+        // У пользователя может не быть доступа.
+        if !self.config.has_access(username, testname) {
+            error!("Пользователь {username} пытался ответить на вопрос теста {testname} не имея не это прав.");
+            return Some(Response::NotAllowedUser);
+        }
+        // Ответ должен относиться к выданной сессии.
+        if let Some(response) = self.check_session(username, testname, session) {
+            return Some(response);
+        }
+        // Тест может быть не запущен
+        if !self.is_user_have_opened_variant(username, testname) {
+            error!("Тест завершен, нельзя отвечать на вопросы: {username}, {testname}.");
+            return Some(Response::End {
+                marks: self.get_marks(username, testname),
+            });
+        }
+        // Ответы после окончания времени не принимаются.
+        if self.is_test_time_is_over(username, testname) {
+            debug!("Время теста {testname} пользователя {username} истекло.");
+            return Some(self.finish(username, testname, AttemptStatus::Expired));
+        }
+        None
+    }
+
+    /// Завершить тест и отправить оценку.
+    fn finish(&mut self, username: &String, testname: &String, status: AttemptStatus) -> Response {
+        self.done_test(username, testname, status);
+        Response::End {
+            marks: self.get_marks(username, testname),
+        }
     }
 
     /// Есть ли у пользователя незаконченный тест.
     fn is_user_have_opened_variant(&self, username: &String, testname: &String) -> bool {
-        self.variants.contains_key(username) && &self.variants[username].testname == testname
+        self.variants.contains_key(&key(username, testname))
     }
 
-    /// Возвращает первый неотвеченный вопрос.
-    fn get_next_question(&mut self, username: &String) -> Response {
-        let variant = &self.variants[username];
-        let id = variant.answers.len();
-        let question = variant.questions[id].clone();
+    /// Запретить начало теста, если у пользователя есть незаконченная попытка
+    /// другого теста, а параллельное прохождение тестов не разрешено.
+    fn check_parallel_tests(&self, username: &String, testname: &String) -> Option<Response> {
+        if self.config.settings().allow_parallel_tests {
+            return None;
+        }
+        let (_, opened) = self
+            .variants
+            .keys()
+            .find(|(user, test)| user == username && test != testname)?;
+        debug!("Пользователь {username} пытался начать тест {testname}, не завершив {opened}.");
+        Some(Response::OtherTestInProgress {
+            testname: opened.clone(),
+        })
+    }
+
+    /// Возвращает вопрос с номером index и данный на него ответ.
+    /// При первом показе вопроса начинается отсчет времени на ответ.
+    fn question_response(&mut self, username: &str, testname: &str, index: usize) -> Response {
+        self.mark_shown(username, testname, index);
+        let variant = &self.variants[&key(username, testname)];
+        let question = variant.questions[index].clone();
+        let question_remaining = self
+            .question_deadline(variant, index)
+            .map(|deadline| (deadline - chrono::Local::now()).num_seconds().max(0) as u64);
         Response::NextQuestion {
             question: question.question,
             answers: question.answers,
+            index,
+            total: variant.questions.len(),
+            remaining_seconds: self.remaining_seconds(variant),
+            question_remaining_seconds: question_remaining,
+            multiple_choice: question.correct_answer.as_array().len() > 1,
+            answer: variant.answers[index].clone(),
+            session: self.find_session(username, testname).unwrap_or_default(),
+        }
+    }
+
+    /// Найти токен открытой сессии пользователя для теста.
+    fn find_session(&self, username: &str, testname: &str) -> Option<String> {
+        self.session_tokens.get(&key(username, testname)).cloned()
+    }
+
+    /// Выдать новый токен сессии для попытки, отозвав прежний. По сети имя
+    /// пользователя не подтверждается, поэтому повторный запуск теста
+    /// не должен раскрывать токен уже идущей попытки.
+    fn open_session(&mut self, username: &str, testname: &str) -> String {
+        let key = key(username, testname);
+        if let Some(old) = self.session_tokens.remove(&key) {
+            self.sessions.remove(&old);
+        }
+        let token = generate_token();
+        self.sessions.insert(token.clone(), key.clone());
+        self.session_tokens.insert(key, token.clone());
+        token
+    }
+
+    /// Проверить токен сессии. Возвращает ответ на запрос с неверным токеном.
+    fn check_session(
+        &mut self,
+        username: &String,
+        testname: &String,
+        session: &Option<String>,
+    ) -> Option<Response> {
+        let key = (username.clone(), testname.clone());
+        if let Some(token) = session {
+            if self.sessions.get(token) == Some(&key) {
+                return None;
+            }
+
+            // Попытка уже завершена, например по истечении времени.
+            if self
+                .closed_sessions
+                .get(token)
+                .is_some_and(|(closed, _)| *closed == key)
+            {
+                return Some(Response::End {
+                    marks: self.get_marks(username, testname),
+                });
+            }
+        }
+        error!("Пользователь {username} предъявил неверный токен сессии теста {testname}.");
+        Some(Response::InvalidSession)
+    }
+
+    /// Закрыть сессию завершенной попытки.
+    fn close_session(&mut self, username: &str, testname: &str) {
+        let key = key(username, testname);
+        if let Some(token) = self.session_tokens.remove(&key) {
+            self.sessions.remove(&token);
+            self.closed_sessions
+                .insert(token, (key, chrono::Local::now()));
         }
     }
 
     /// Запускает новый тест.
     fn start_test(&mut self, username: &String, testname: &String) {
         let variant = self.generate_variant(username, testname);
-        self.create_test_record(username, variant);
+        self.create_test_record(variant);
         debug!("Пользователь {username} начал тестирование {testname}.");
     }
 
     /// Создать вариант теста.
+    /// Зерно генератора сохраняется в варианте, чтобы вариант можно было воспроизвести.
     fn generate_variant(&self, username: &String, testname: &String) -> Variant {
-        let test_settings = self.config.test_settings(testname).unwrap();
-
-        let mut vec: Vec<usize> = (0..self.config.questions_count(testname).unwrap()).collect();
-        vec.shuffle(&mut thread_rng());
-
-        let mut questions: Vec<Question> = vec![];
-        let mut questions_number = test_settings.questions_number;
-        if questions_number <= 0
-            || questions_number >= self.config.questions_count(testname).unwrap()
-        {
-            questions_number = self.config.questions_count(testname).unwrap();
-        }
-        for i in 0..questions_number {
-            questions.push(self.config.question(testname, vec[i]).unwrap().clone());
-        }
+        let seed = thread_rng().gen::<u64>();
+        let questions = variant_questions(self.config.as_ref(), testname, seed);
 
         Variant {
             username: username.clone(),
             testname: testname.clone(),
             start_timestamp: chrono::offset::Local::now(),
+            answers: vec![None; questions.len()],
+            shown_at: vec![None; questions.len()],
             questions,
-            answers: vec![],
+            seed: Some(seed),
+            test_version: self.config.test_version(testname),
         }
     }
 
     /// Запомнить сгенерированный вариант теста.
-    fn create_test_record(&mut self, username: &String, variant: Variant) {
-        self.variants.insert(username.clone(), variant);
+    fn create_test_record(&mut self, variant: Variant) {
+        self.db.save_open_variant(&variant);
+        self.variants.insert(
+            (variant.username.clone(), variant.testname.clone()),
+            variant,
+        );
+    }
+
+    /// Время окончания попытки: окончание времени теста или закрытие теста.
+    fn deadline(&self, variant: &Variant) -> chrono::DateTime<chrono::Local> {
+        let test_settings = self.config.test_settings(&variant.testname).unwrap();
+        let deadline = variant.start_timestamp
+            + chrono::Duration::new(test_settings.test_duration_minutes * 60, 0).unwrap();
+        match test_settings
+            .close_at
+            .and_then(|close_at| close_at.and_local_timezone(chrono::Local).earliest())
+        {
+            Some(close_at) => deadline.min(close_at),
+            None => deadline,
+        }
+    }
+
+    /// Время до окончания попытки в секундах.
+    fn remaining_seconds(&self, variant: &Variant) -> u64 {
+        let remaining = self.deadline(variant) - chrono::Local::now();
+        remaining.num_seconds().max(0) as u64
     }
 
     /// Закончилось ли время тестирования?
     fn is_test_time_is_over(&self, username: &String, testname: &String) -> bool {
-        let test_settings = self.config.test_settings(testname).unwrap();
-        let variant = &self.variants[username];
+        chrono::Local::now() > self.deadline(&self.variants[&key(username, testname)])
+    }
+
+    /// Запомнить время первого показа вопроса.
+    fn mark_shown(&mut self, username: &str, testname: &str, index: usize) {
+        let variant = self.variants.get_mut(&key(username, testname)).unwrap();
+        // Попытки, сохраненные предыдущей версией сервера, не содержат времени показа.
+        variant.shown_at.resize(variant.questions.len(), None);
+        if variant.shown_at[index].is_none() {
+            variant.shown_at[index] = Some(chrono::Local::now());
+            self.db.save_open_variant(variant);
+        }
+    }
+
+    /// Время на ответ на вопрос в секундах, если оно ограничено.
+    fn question_limit(&self, variant: &Variant, index: usize) -> Option<u64> {
+        variant.questions[index].time_limit_seconds.or_else(|| {
+            let test_settings = self.config.test_settings(&variant.testname).unwrap();
+            let seconds = test_settings.question_duration_seconds;
+            (seconds > 0).then_some(seconds)
+        })
+    }
+
+    /// Время окончания ответа на вопрос. None, если время не ограничено
+    /// или вопрос ещё не показан.
+    fn question_deadline(
+        &self,
+        variant: &Variant,
+        index: usize,
+    ) -> Option<chrono::DateTime<chrono::Local>> {
+        let shown_at = (*variant.shown_at.get(index)?)?;
+        let limit = self.question_limit(variant, index)?;
+        Some(shown_at + chrono::Duration::seconds(limit as i64))
+    }
+
+    /// Истекло ли время ответа на вопрос.
+    fn is_question_closed(&self, variant: &Variant, index: usize) -> bool {
+        self.question_deadline(variant, index)
+            .is_some_and(|deadline| {
+                chrono::Local::now() > deadline + chrono::Duration::seconds(QUESTION_GRACE_SECONDS)
+            })
+    }
 
-        chrono::Local::now() - variant.start_timestamp
-            > chrono::Duration::new(test_settings.test_duration_minutes * 60, 0).unwrap()
+    /// Номер первого неотвеченного вопроса варианта, время на который не истекло.
+    fn first_unanswered(&self, username: &str, testname: &str) -> Option<usize> {
+        let variant = &self.variants[&key(username, testname)];
+        (0..variant.questions.len()).find(|&index| {
+            variant.answers[index].is_none() && !self.is_question_closed(variant, index)
+        })
     }
 
-    /// Содержит ли вариант ещё неотвеченные вопросы.
-    fn is_next_question(&self, username: &String) -> bool {
-        let current_question = self.variants[username].answers.len();
-        return current_question < self.variants[username].questions.len();
+    /// Вопрос, на который отвечает PutAnswer: последний показанный вопрос без ответа.
+    /// Ответ на вопрос, который еще не показывался, не принимается: иначе отсчет
+    /// времени на ответ не начался бы.
+    fn current_question(&self, username: &str, testname: &str) -> Option<usize> {
+        let variant = &self.variants[&key(username, testname)];
+        (0..variant.questions.len())
+            .filter(|&index| variant.answers[index].is_none())
+            .filter_map(|index| Some(((*variant.shown_at.get(index)?)?, index)))
+            .max()
+            .map(|(_, index)| index)
     }
 
-    /// Сохранить ответ пользователя на последний неотвеченный вопрос.
-    fn push_answer_on_current_question(&mut self, username: &String, answer: &Answer) {
-        let variant = self.variants.get_mut(username).unwrap();
-        variant.answers.push(answer.clone());
+    /// Есть ли в варианте вопрос с номером index.
+    fn has_question(&self, username: &str, testname: &str, index: usize) -> bool {
+        index < self.variants[&key(username, testname)].questions.len()
+    }
+
+    /// Сохранить ответ пользователя на вопрос с номером index.
+    /// Возвращает false, если время ответа на вопрос истекло.
+    fn set_answer_on_question(
+        &mut self,
+        username: &String,
+        testname: &String,
+        index: usize,
+        answer: &Answer,
+    ) -> bool {
+        if self.is_question_closed(&self.variants[&key(username, testname)], index) {
+            debug!(
+                "Время ответа на вопрос {index} теста {testname} пользователя {username} истекло."
+            );
+            return false;
+        }
+        let variant = self.variants.get_mut(&key(username, testname)).unwrap();
+        let answer = answer.in_range(variant.questions[index].answers.len());
+        variant.answers[index] = if answer.as_array().is_empty() {
+            None
+        } else {
+            Some(answer)
+        };
+        self.db.save_open_variant(variant);
+        true
     }
 
     /// Завершить тест
-    fn done_test(&mut self, username: &String, testname: &String) {
-        let mark = self.calculate_mark(username);
-        let start_time = self.variants[username].start_timestamp.to_string();
+    fn done_test(&mut self, username: &String, testname: &String, status: AttemptStatus) {
+        let variant = self.variants.remove(&key(username, testname)).unwrap();
+        let mut mark = calculate_mark(&variant);
+        if let (Some(settings), Some(percent)) =
+            (self.config.test_settings(testname), mark.percent())
+        {
+            mark.grade = settings.grade(percent);
+            mark.passed = settings.passed(percent);
+        }
+        let start_time = variant.start_timestamp.to_string();
         let end_time = chrono::Local::now().to_string();
         self.db.append_mark(
             username,
@@ -256,23 +728,165 @@ impl Examiner {
             mark,
             &start_time,
             &end_time,
-            &self.variants[username],
+            &variant,
+            status,
         );
         debug!(
-            "Пользователь {username} завершил тест {testname}: {:?}.",
-            self.variants[username]
+            "Пользователь {username} завершил тест {testname} ({}): {variant:?}.",
+            status.as_str()
         );
-        self.variants.remove(username);
+        self.db.remove_open_variant(username, testname);
+        self.close_session(username, testname);
     }
+}
 
-    /// Посчитать оценку за тест.
-    fn calculate_mark(&mut self, username: &String) -> f32 {
-        let variant = self.variants.get_mut(username).unwrap();
-        let mut result: f32 = 0.0;
-        for i in 0..variant.answers.len() {
-            result += check_answer(&variant.answers[i], &variant.questions[i].correct_answer);
+/// Вопросы варианта теста testname, выбранные генератором с зерном seed.
+/// При одинаковых зерне, версии теста и настройках результат одинаков:
+/// ChaCha8Rng не меняет последовательность между версиями библиотеки.
+pub fn variant_questions(config: &dyn Config, testname: &String, seed: u64) -> Vec<Question> {
+    let test_settings = config.test_settings(testname).unwrap();
+    let questions_count = config.questions_count(testname).unwrap();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let indices = if test_settings.sections.is_empty() {
+        let mut vec: Vec<usize> = (0..questions_count).collect();
+        vec.shuffle(&mut rng);
+
+        let mut questions_number = test_settings.questions_number;
+        if questions_number == 0 || questions_number >= questions_count {
+            questions_number = questions_count;
+        }
+        vec.truncate(questions_number);
+        if test_settings.fixed_question_order {
+            vec.sort();
+        }
+        vec
+    } else {
+        draw_sections(config.sections(testname).unwrap(), &test_settings, &mut rng)
+    };
+
+    indices
+        .iter()
+        .map(|&i| {
+            let mut question = config.question(testname, i).unwrap();
+            // Правило оценивания сохраняется в варианте вместе с вопросом.
+            question.scoring.get_or_insert(test_settings.scoring);
+            if test_settings.shuffle_answers {
+                shuffle_answers(question, &mut rng)
+            } else {
+                question
+            }
+        })
+        .collect()
+}
+
+/// Выбрать вопросы из каждого раздела в количестве, указанном в настройках теста.
+fn draw_sections(
+    sections: Vec<Section>,
+    test_settings: &TestSettings,
+    rng: &mut ChaCha8Rng,
+) -> Vec<usize> {
+    let mut blocks: Vec<Vec<usize>> = sections
+        .into_iter()
+        .map(|section| {
+            let mut questions = section.questions;
+            questions.shuffle(rng);
+            let number = test_settings.section_questions_number(&section.name);
+            if number > 0 {
+                questions.truncate(number);
+            }
+            if test_settings.fixed_question_order {
+                questions.sort();
+            }
+            questions
+        })
+        .collect();
+    if test_settings.shuffle_sections {
+        blocks.shuffle(rng);
+    }
+    blocks.concat()
+}
+
+/// Перемешать ответы на вопрос. Закрепленные ответы остаются на своих местах,
+/// номера правильных ответов пересчитываются, порядок ответов сохраняется в answer_order.
+fn shuffle_answers(question: Question, rng: &mut ChaCha8Rng) -> Question {
+    let mut order: Vec<usize> = (0..question.answers.len()).collect();
+    let positions: Vec<usize> = order
+        .iter()
+        .copied()
+        .filter(|i| !question.pinned_answers.contains(i))
+        .collect();
+    let mut shuffled = positions.clone();
+    shuffled.shuffle(rng);
+    for (position, source) in positions.into_iter().zip(shuffled) {
+        order[position] = source;
+    }
+
+    let correct = question.correct_answer.as_array();
+    Question {
+        answers: order.iter().map(|&i| question.answers[i].clone()).collect(),
+        correct_answer: Answer::new(
+            (0..order.len())
+                .filter(|&position| correct.contains(&order[position]))
+                .collect(),
+        ),
+        answer_order: order,
+        ..question
+    }
+}
+
+/// Ключ попытки в хранилище вариантов.
+fn key(username: &str, testname: &str) -> (String, String) {
+    (username.to_string(), testname.to_string())
+}
+
+/// Посчитать оценку за тест: балл за вопрос умножается на вес вопроса.
+/// Вопросы без ответа оцениваются нулем.
+fn calculate_mark(variant: &Variant) -> Mark {
+    let mut points: f32 = 0.0;
+    for (answer, question) in variant.answers.iter().zip(&variant.questions) {
+        if let Some(answer) = answer {
+            points += question.weight * question_score(answer, question);
+        }
+    }
+    let max_points = variant
+        .questions
+        .iter()
+        .map(|question| question.weight)
+        .sum();
+    Mark::new(points, max_points)
+}
+
+/// Сгенерировать случайный токен сессии.
+fn generate_token() -> String {
+    let mut rng = thread_rng();
+    (0..16)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+/// Балл за ответ на вопрос по правилу оценивания вопроса.
+fn question_score(answer: &Answer, question: &Question) -> f32 {
+    let correct_answer = question.correct_answer.as_array();
+    let answer = answer.in_range(question.answers.len()).as_array();
+    let n_true = correct_answer.len().max(1) as f32;
+    let right = answer.iter().filter(|a| correct_answer.contains(a)).count() as f32;
+    let wrong = answer.len() as f32 - right;
+
+    match question.scoring.unwrap_or_default() {
+        Scoring::Partial => check_answer(&Answer::new(answer), &question.correct_answer),
+        Scoring::AllOrNothing => {
+            if answer == correct_answer {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        Scoring::NoPenalty => (right / n_true).clamp(0.0, 1.0),
+        Scoring::Negative => {
+            let n_false = (question.answers.len() - correct_answer.len()).max(1) as f32;
+            (right / n_true - wrong / n_false).clamp(-1.0, 1.0)
         }
-        result
     }
 }
 
@@ -301,7 +915,7 @@ fn check_answer(answer: &Answer, correct_answer: &Answer) -> f32 {
 #[cfg(test)]
 mod tests {
     use crate::network::Marks;
-    use crate::schema::Variant;
+    use crate::schema::{AttemptStatus, Mark, Scoring, Variant, WindowState};
     use crate::{
         network::Response,
         schema::{Answer, Question},
@@ -309,52 +923,135 @@ mod tests {
         Config, Database,
     };
 
-    use super::{check_answer, Examiner};
+    use super::{
+        calculate_mark, check_answer, draw_sections, question_score, shuffle_answers,
+        variant_questions, Examiner, CLOSED_SESSION_GRACE_MINUTES,
+    };
+    use crate::settings::ShutdownPolicy;
+    use crate::settings::{GradeSettings, ResultPolicy, Section, SectionSettings};
+    use chrono::NaiveDateTime;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::{
+        cell::{Cell, RefCell},
+        collections::{HashMap, HashSet},
+        rc::Rc,
+    };
 
-    struct TDatabase {}
+    /// База данных в памяти. Клоны разделяют общее состояние.
+    #[derive(Default, Clone)]
+    struct TDatabase {
+        open_variants: Rc<RefCell<HashMap<(String, String), Variant>>>,
+        finished: Rc<RefCell<Vec<(String, String)>>>,
+        statuses: Rc<RefCell<Vec<AttemptStatus>>>,
+        last_end: Rc<RefCell<HashMap<String, chrono::DateTime<chrono::FixedOffset>>>>,
+    }
 
     impl Database for TDatabase {
         fn attempts_counter(&mut self, _username: &String, _testname: &String) -> u32 {
             1
         }
 
-        fn marks(&mut self, _username: &String, _testname: &String) -> Vec<f32> {
-            vec![3.0]
+        fn last_attempt_end(
+            &mut self,
+            _username: &String,
+            testname: &String,
+        ) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+            self.last_end.borrow().get(testname).copied()
+        }
+
+        fn marks(&mut self, _username: &String, _testname: &String) -> Vec<Mark> {
+            vec![Mark::new(3.0, 4.0)]
         }
 
         /// Сохранить баллы за тест testname для пользователя username.
         fn append_mark(
             &mut self,
-            _username: &String,
-            _testname: &String,
-            _mark: f32,
+            username: &String,
+            testname: &String,
+            _mark: Mark,
             _start_timestamp: &String,
-            _end_timestamp: &String,
+            end_timestamp: &String,
             _variant: &Variant,
+            status: AttemptStatus,
         ) {
+            if let Ok(end) =
+                chrono::DateTime::parse_from_str(end_timestamp, "%Y-%m-%d %H:%M:%S.%f %z")
+            {
+                if status != AttemptStatus::Interrupted {
+                    self.last_end.borrow_mut().insert(testname.clone(), end);
+                }
+            }
+            self.finished
+                .borrow_mut()
+                .push((username.clone(), testname.clone()));
+            self.statuses.borrow_mut().push(status);
+        }
+
+        fn save_open_variant(&mut self, variant: &Variant) {
+            self.open_variants.borrow_mut().insert(
+                (variant.username.clone(), variant.testname.clone()),
+                variant.clone(),
+            );
         }
+
+        fn remove_open_variant(&mut self, username: &String, testname: &String) {
+            self.open_variants
+                .borrow_mut()
+                .remove(&(username.clone(), testname.clone()));
+        }
+
+        fn load_open_variants(&mut self) -> Vec<Variant> {
+            self.open_variants.borrow().values().cloned().collect()
+        }
+    }
+
+    /// Время открытия и закрытия тестов.
+    type Window = (Option<NaiveDateTime>, Option<NaiveDateTime>);
+
+    struct TConfig {
+        allow_parallel_tests: bool,
+        /// Окно доступности можно изменить после создания экзаменатора.
+        window: Rc<Cell<Window>>,
+    }
+
+    /// Тесты math и physics одинаковы, в списке тестов пользователя только math.
+    fn is_test(testname: &str) -> bool {
+        testname == "math" || testname == "physics"
     }
 
-    struct TConfig {}
     impl Config for TConfig {
         fn has_user(&self, username: &String) -> bool {
             *username == "student".to_string()
         }
 
         fn has_test(&self, testname: &String) -> bool {
-            *testname == "math".to_string()
+            is_test(testname)
         }
 
         fn test_settings(&self, testname: &String) -> Option<settings::TestSettings> {
-            if *testname == "math".to_string() {
+            if is_test(testname) {
                 Some(settings::TestSettings {
                     caption: "math".to_string(),
                     questions_number: 1,
                     test_duration_minutes: 1,
                     number_of_attempts: 3,
+                    retry_cooldown_minutes: if testname == "physics" { 10 } else { 0 },
                     show_results: true,
                     allowed_users: Some(vec!["student".to_string()]),
                     allowed_users_path: None,
+                    allow_abandon: testname == "physics",
+                    question_duration_seconds: 0,
+                    shuffle_answers: false,
+                    scoring: Scoring::Partial,
+                    sections: vec![],
+                    fixed_question_order: false,
+                    shuffle_sections: false,
+                    grades: vec![],
+                    pass_percent: None,
+                    official_result: ResultPolicy::Best,
+                    open_at: self.window.get().0,
+                    close_at: self.window.get().1,
                 })
             } else {
                 None
@@ -362,7 +1059,7 @@ mod tests {
         }
 
         fn test_banner(&self, testname: &String) -> Option<String> {
-            if *testname == "math".to_string() {
+            if is_test(testname) {
                 Some("description".to_string())
             } else {
                 None
@@ -370,11 +1067,16 @@ mod tests {
         }
 
         fn question(&self, testname: &String, question_id: usize) -> Option<Question> {
-            if *testname == "math".to_string() && question_id == 0 {
+            if is_test(testname) && question_id == 0 {
                 Some(Question {
                     question: "2+2".to_string(),
                     answers: vec!["4".to_string(), "5".to_string()],
                     correct_answer: Answer::new(vec![0]),
+                    time_limit_seconds: None,
+                    pinned_answers: vec![],
+                    answer_order: vec![],
+                    weight: 1.0,
+                    scoring: None,
                 })
             } else {
                 None
@@ -383,15 +1085,28 @@ mod tests {
 
         /// Получить количество вопросов в тесте.
         fn questions_count(&self, testname: &String) -> Option<usize> {
-            if *testname == "math".to_string() {
+            if is_test(testname) {
                 Some(1)
             } else {
                 None
             }
         }
 
+        fn test_version(&self, testname: &String) -> Option<String> {
+            is_test(testname).then(|| "1".to_string())
+        }
+
+        fn sections(&self, testname: &String) -> Option<Vec<Section>> {
+            is_test(testname).then(|| {
+                vec![Section {
+                    name: "".to_string(),
+                    questions: vec![0],
+                }]
+            })
+        }
+
         fn answer(&self, testname: &String, question_id: usize) -> Option<Answer> {
-            if *testname == "math".to_string() && question_id == 0 {
+            if is_test(testname) && question_id == 0 {
                 Some(Answer::new(vec![0]))
             } else {
                 None
@@ -399,7 +1114,7 @@ mod tests {
         }
 
         fn has_access(&self, username: &String, testname: &String) -> bool {
-            *username == "student".to_string() && *testname == "math".to_string()
+            *username == "student".to_string() && is_test(testname)
         }
 
         fn user_tests_list(&self, _username: &String) -> Vec<String> {
@@ -411,6 +1126,20 @@ mod tests {
                 tests_directory_path: "example-config".to_string(),
                 result_path: "marks.db".to_string(),
                 server_address: "127.0.0.1:8080".to_string(),
+                socket_path: "".to_string(),
+                http_address: "".to_string(),
+                tls_cert_path: "".to_string(),
+                tls_key_path: "".to_string(),
+                allow_plaintext: false,
+                user_rate_limit: 0.0,
+                user_burst: 0,
+                host_rate_limit: 0.0,
+                host_burst: 0,
+                allow_parallel_tests: self.allow_parallel_tests,
+                shutdown_policy: ShutdownPolicy::Checkpoint,
+                max_message_size: 1_048_576,
+                read_timeout_seconds: 10,
+                server_threads: 32,
                 tests: vec![self.test_settings(&"math".to_string()).unwrap()],
                 log_level: "debug".to_string(),
             }
@@ -418,21 +1147,57 @@ mod tests {
     }
 
     fn get_examiner() -> Examiner {
-        let config = TConfig {};
-        let database = TDatabase {};
+        examiner_with_database(TDatabase::default())
+    }
+
+    fn examiner_with_database(database: TDatabase) -> Examiner {
+        let config = TConfig {
+            allow_parallel_tests: false,
+            window: Default::default(),
+        };
         Examiner::new(Box::new(config), Box::new(database))
     }
 
+    fn start_session(examiner: &mut Examiner) -> Option<String> {
+        start_test_session(examiner, "math")
+    }
+
+    fn start_test_session(examiner: &mut Examiner, testname: &str) -> Option<String> {
+        match examiner.banner_to_start_test(&"student".to_string(), &testname.to_string()) {
+            Response::TestStarted { session, .. } => Some(session),
+            resp => panic!("Ожидалось описание теста: {resp:?}"),
+        }
+    }
+
     #[test]
     fn examiner_description() {
         let mut examiner = get_examiner();
         let resp = examiner.banner_to_start_test(&"student".to_string(), &"math".to_string());
-        assert_eq!(
-            resp,
-            Response::TestStarted {
-                banner: "description".to_string()
-            }
+        let Response::TestStarted { banner, session } = resp else {
+            panic!("Ожидалось описание теста: {resp:?}");
+        };
+        assert_eq!(banner, "description".to_string());
+        assert_eq!(session.len(), 32);
+
+        // Повторный запуск выдает новую сессию, прежний токен отзывается.
+        let resp = examiner.banner_to_start_test(&"student".to_string(), &"math".to_string());
+        let Response::TestStarted {
+            session: new_session,
+            ..
+        } = resp
+        else {
+            panic!("Ожидалось описание теста: {resp:?}");
+        };
+        assert_ne!(new_session, session);
+        let resp =
+            examiner.next_question(&"student".to_string(), &"math".to_string(), &Some(session));
+        assert_eq!(resp, Response::InvalidSession);
+        let resp = examiner.next_question(
+            &"student".to_string(),
+            &"math".to_string(),
+            &Some(new_session),
         );
+        assert!(matches!(resp, Response::NextQuestion { .. }));
 
         let resp = examiner.banner_to_start_test(&"student2".to_string(), &"math".to_string());
         assert_eq!(resp, Response::NotAllowedUser);
@@ -454,7 +1219,15 @@ mod tests {
         assert_eq!(
             resp,
             Response::AvaliableTests {
-                tests: vec![("math".to_string(), Marks::Marks { marks: vec![3.0] })]
+                tests: vec![(
+                    "math".to_string(),
+                    Marks::Marks {
+                        marks: vec![Mark::new(3.0, 4.0)],
+                        official: Some(Mark::new(3.0, 4.0))
+                    },
+                    settings::TestSettings::default()
+                        .availability(chrono::Local::now().naive_local())
+                )]
             }
         );
     }
@@ -462,16 +1235,21 @@ mod tests {
     #[test]
     fn examiner_put_answer() {
         let mut examiner = get_examiner();
+        let session = start_session(&mut examiner);
         let resp = examiner.put_answer(
             &"student".to_string(),
             &"math".to_string(),
             &Answer::new(vec![1]),
+            &session,
         );
 
         assert_eq!(
             resp,
             Response::End {
-                marks: Marks::Marks { marks: vec![3.0] }
+                marks: Marks::Marks {
+                    marks: vec![Mark::new(3.0, 4.0)],
+                    official: Some(Mark::new(3.0, 4.0))
+                }
             }
         );
 
@@ -479,6 +1257,7 @@ mod tests {
             &"student2".to_string(),
             &"math".to_string(),
             &Answer::new(vec![1]),
+            &session,
         );
 
         assert_eq!(resp, Response::NotAllowedUser);
@@ -487,26 +1266,736 @@ mod tests {
             &"student".to_string(),
             &"math2".to_string(),
             &Answer::new(vec![1]),
+            &session,
         );
 
         assert_eq!(resp, Response::NotAllowedUser);
     }
 
+    #[test]
+    fn examiner_session() {
+        let mut examiner = get_examiner();
+        let student = "student".to_string();
+        let math = "math".to_string();
+
+        let resp = examiner.next_question(&student, &math, &None);
+        assert_eq!(resp, Response::InvalidSession);
+
+        let session = start_session(&mut examiner);
+        let resp = examiner.next_question(&student, &math, &Some("forged".to_string()));
+        assert_eq!(resp, Response::InvalidSession);
+
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &None);
+        assert_eq!(resp, Response::InvalidSession);
+
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(
+            matches!(resp, Response::NextQuestion { session: s, .. } if session.as_ref() == Some(&s))
+        );
+
+        // После завершения попытки сессия закрывается.
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+
+        // Новая попытка получает новый токен.
+        let new_session = start_session(&mut examiner);
+        assert_ne!(new_session, session);
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::End { .. }));
+    }
+
+    #[test]
+    fn examiner_closed_session_pruned() {
+        let mut examiner = get_examiner();
+        let student = "student".to_string();
+        let math = "math".to_string();
+
+        let session = start_session(&mut examiner);
+        examiner.next_question(&student, &math, &session);
+        examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+        examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+
+        // Недавно закрытая сессия сохраняется.
+        examiner.variant_collector();
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::End { .. }));
+
+        let token = session.clone().unwrap();
+        examiner.closed_sessions.get_mut(&token).unwrap().1 -=
+            chrono::Duration::minutes(CLOSED_SESSION_GRACE_MINUTES + 1);
+        examiner.variant_collector();
+        assert!(examiner.closed_sessions.is_empty());
+        let resp = examiner.next_question(&student, &math, &session);
+        assert_eq!(resp, Response::InvalidSession);
+    }
+
     #[test]
     fn examiner_next_question() {
         let mut examiner = get_examiner();
-        let resp = examiner.next_question(&"username".to_string(), &"math".to_string());
+        let session = start_session(&mut examiner);
+        let resp = examiner.next_question(&"username".to_string(), &"math".to_string(), &session);
         assert_eq!(resp, Response::NotAllowedUser);
 
-        let resp = examiner.next_question(&"student".to_string(), &"testname".to_string());
+        let resp =
+            examiner.next_question(&"student".to_string(), &"testname".to_string(), &session);
         assert_eq!(resp, Response::NotAllowedUser);
 
-        let resp = examiner.next_question(&"student".to_string(), &"math".to_string());
-        let true_resp = Response::NextQuestion {
-            question: "2+2".to_string(),
-            answers: vec!["4".to_string(), "5".to_string()],
+        let resp = examiner.next_question(&"student".to_string(), &"math".to_string(), &session);
+        let Response::NextQuestion {
+            question,
+            answers,
+            index,
+            total,
+            remaining_seconds,
+            question_remaining_seconds,
+            multiple_choice,
+            answer,
+            session: resp_session,
+        } = resp
+        else {
+            panic!("Ожидался вопрос: {resp:?}");
         };
-        assert_eq!(resp, true_resp);
+        assert_eq!(question, "2+2".to_string());
+        assert_eq!(answers, vec!["4".to_string(), "5".to_string()]);
+        assert_eq!((index, total), (0, 1));
+        // Длительность теста - одна минута.
+        assert!((58..=60).contains(&remaining_seconds));
+        assert_eq!(question_remaining_seconds, None);
+        assert!(!multiple_choice);
+        assert_eq!(answer, None);
+        assert_eq!(Some(resp_session), session);
+    }
+
+    #[test]
+    fn examiner_shutdown_checkpoint() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+
+        let session = start_session(&mut examiner);
+        examiner.next_question(&student, &math, &session);
+        examiner.shutdown(ShutdownPolicy::Checkpoint);
+        assert_eq!(database.open_variants.borrow().len(), 1);
+        assert!(database.finished.borrow().is_empty());
+
+        // После перезапуска попытка продолжается в новой сессии.
+        let mut examiner = examiner_with_database(database.clone());
+        let session = start_session(&mut examiner);
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+        assert_eq!(*database.finished.borrow(), vec![(student, math)]);
+        assert!(database.open_variants.borrow().is_empty());
+    }
+
+    #[test]
+    fn examiner_crash_recovery() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+
+        // Вариант сохраняется сразу при запуске теста, без остановки сервера.
+        let session = start_session(&mut examiner);
+        examiner.next_question(&student, &math, &session);
+        drop(examiner);
+        let saved = database.open_variants.borrow()[&(student.clone(), math.clone())].clone();
+
+        let mut examiner = examiner_with_database(database.clone());
+        let session = start_session(&mut examiner);
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::NextQuestion { question, .. } if question == "2+2"));
+        assert_eq!(
+            database.open_variants.borrow()[&(student.clone(), math.clone())].start_timestamp,
+            saved.start_timestamp
+        );
+
+        // Время теста отсчитывается от начала попытки до перезапуска.
+        let mut expired = saved;
+        expired.start_timestamp -= chrono::Duration::minutes(2);
+        database
+            .open_variants
+            .borrow_mut()
+            .insert((student.clone(), math.clone()), expired);
+        let mut examiner = examiner_with_database(database.clone());
+        examiner.variant_collector();
+        assert_eq!(*database.finished.borrow(), vec![(student, math)]);
+        assert_eq!(*database.statuses.borrow(), vec![AttemptStatus::Expired]);
+        assert!(database.open_variants.borrow().is_empty());
+    }
+
+    #[test]
+    fn examiner_reproducible_variant() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let session = start_session(&mut examiner);
+        examiner.next_question(&"student".to_string(), &"math".to_string(), &session);
+
+        let saved =
+            database.open_variants.borrow()[&("student".to_string(), "math".to_string())].clone();
+        assert_eq!(saved.test_version, Some("1".to_string()));
+        let config = TConfig {
+            allow_parallel_tests: false,
+            window: Default::default(),
+        };
+        let questions = variant_questions(&config, &"math".to_string(), saved.seed.unwrap());
+        assert_eq!(questions, saved.questions);
+    }
+
+    #[test]
+    fn examiner_parallel_tests_rejected() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let physics = "physics".to_string();
+
+        let math_session = start_test_session(&mut examiner, "math");
+        examiner.next_question(&student, &math, &math_session);
+
+        let resp = examiner.banner_to_start_test(&student, &physics);
+        assert_eq!(
+            resp,
+            Response::OtherTestInProgress {
+                testname: math.clone()
+            }
+        );
+
+        // После завершения теста можно начать другой.
+        examiner.put_answer(&student, &math, &Answer::new(vec![0]), &math_session);
+        let physics_session = start_test_session(&mut examiner, "physics");
+        let resp = examiner.next_question(&student, &physics, &physics_session);
+        assert!(matches!(resp, Response::NextQuestion { .. }));
+    }
+
+    #[test]
+    fn examiner_parallel_tests_allowed() {
+        let database = TDatabase::default();
+        let config = TConfig {
+            allow_parallel_tests: true,
+            window: Default::default(),
+        };
+        let mut examiner = Examiner::new(Box::new(config), Box::new(database.clone()));
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let physics = "physics".to_string();
+
+        let math_session = start_test_session(&mut examiner, "math");
+        examiner.next_question(&student, &math, &math_session);
+        let physics_session = start_test_session(&mut examiner, "physics");
+        examiner.next_question(&student, &physics, &physics_session);
+        assert_eq!(database.open_variants.borrow().len(), 2);
+
+        // Попытки не мешают друг другу.
+        let resp = examiner.put_answer(&student, &physics, &Answer::new(vec![0]), &physics_session);
+        assert!(matches!(resp, Response::End { .. }));
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![1]), &math_session);
+        assert!(matches!(resp, Response::End { .. }));
+        assert_eq!(
+            *database.finished.borrow(),
+            vec![(student.clone(), physics), (student, math)]
+        );
+    }
+
+    /// Попытка теста math из двух вопросов: 2+2 и 2*3.
+    fn two_questions_variant() -> Variant {
+        let question = |text: &str, correct: usize| Question {
+            question: text.to_string(),
+            answers: vec!["4".to_string(), "6".to_string()],
+            correct_answer: Answer::new(vec![correct]),
+            time_limit_seconds: None,
+            pinned_answers: vec![],
+            answer_order: vec![],
+            weight: 1.0,
+            scoring: None,
+        };
+        Variant {
+            questions: vec![question("2+2", 0), question("2*3", 1)],
+            answers: vec![None, None],
+            ..Variant::new("student", "math")
+        }
+    }
+
+    #[test]
+    fn examiner_navigation() {
+        let database = TDatabase::default();
+        database.open_variants.borrow_mut().insert(
+            ("student".to_string(), "math".to_string()),
+            two_questions_variant(),
+        );
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let session = start_session(&mut examiner);
+
+        let resp = examiner.answers_state(&student, &math, &session);
+        assert!(matches!(resp, Response::AnswersState { answered, .. }
+            if answered == vec![false, false]));
+
+        // Вопросы можно пропускать и отвечать в любом порядке.
+        let resp = examiner.get_question(&student, &math, 1, &session);
+        assert!(
+            matches!(resp, Response::NextQuestion { question, index: 1, answer: None, .. }
+            if question == "2*3")
+        );
+        let resp = examiner.set_answer(&student, &math, 1, &Answer::new(vec![1]), &session);
+        assert_eq!(resp, Response::Ok);
+        let resp = examiner.get_question(&student, &math, 1, &session);
+        assert!(
+            matches!(resp, Response::NextQuestion { answer: Some(answer), .. }
+            if answer == Answer::new(vec![1]))
+        );
+
+        // Несуществующий вопрос.
+        let resp = examiner.get_question(&student, &math, 2, &session);
+        assert_eq!(resp, Response::ResponseError);
+        let resp = examiner.set_answer(&student, &math, 2, &Answer::new(vec![0]), &session);
+        assert_eq!(resp, Response::ResponseError);
+
+        // Ответ можно изменить или отменить.
+        examiner.set_answer(&student, &math, 0, &Answer::new(vec![1]), &session);
+        examiner.set_answer(&student, &math, 0, &Answer::new(vec![]), &session);
+        // Номера, которых нет среди ответов, отбрасываются.
+        examiner.set_answer(&student, &math, 0, &Answer::new(vec![5, 9]), &session);
+        let resp = examiner.answers_state(&student, &math, &session);
+        assert!(matches!(resp, Response::AnswersState { answered, .. }
+            if answered == vec![false, true]));
+        let saved = database.open_variants.borrow()[&(student.clone(), math.clone())].clone();
+        assert_eq!(saved.answers, vec![None, Some(Answer::new(vec![1]))]);
+        assert_eq!(calculate_mark(&saved), Mark::new(1.0, 2.0));
+
+        // Следующий вопрос - первый неотвеченный.
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::NextQuestion { index: 0, .. }));
+
+        let resp = examiner.finish_test(&student, &math, &session);
+        assert!(matches!(resp, Response::End { .. }));
+        assert_eq!(database.finished.borrow().len(), 1);
+        let resp = examiner.set_answer(&student, &math, 0, &Answer::new(vec![0]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+    }
+
+    #[test]
+    fn examiner_question_time_limit() {
+        let database = TDatabase::default();
+        let mut variant = two_questions_variant();
+        variant.questions[0].time_limit_seconds = Some(10);
+        variant.questions[1].time_limit_seconds = Some(10);
+        // Первый вопрос показан давно, время ответа на него истекло.
+        variant.shown_at = vec![
+            Some(chrono::Local::now() - chrono::Duration::seconds(30)),
+            None,
+        ];
+        database
+            .open_variants
+            .borrow_mut()
+            .insert(("student".to_string(), "math".to_string()), variant);
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let session = start_session(&mut examiner);
+
+        let resp = examiner.set_answer(&student, &math, 0, &Answer::new(vec![0]), &session);
+        assert_eq!(resp, Response::QuestionTimeOver);
+        let resp = examiner.get_question(&student, &math, 0, &session);
+        assert!(matches!(
+            resp,
+            Response::NextQuestion {
+                question_remaining_seconds: Some(0),
+                ..
+            }
+        ));
+
+        // Закрытый вопрос пропускается, отсчет времени второго вопроса начинается при показе.
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(
+            matches!(resp, Response::NextQuestion { index: 1, question_remaining_seconds: Some(seconds), .. }
+            if (9..=10).contains(&seconds))
+        );
+        let resp = examiner.answers_state(&student, &math, &session);
+        assert!(matches!(resp, Response::AnswersState { closed, .. }
+            if closed == vec![true, false]));
+
+        // Ответ засчитывается последнему показанному вопросу, закрытый вопрос остается без ответа.
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![1]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+        assert_eq!(database.finished.borrow().len(), 1);
+        assert_eq!(*database.statuses.borrow(), vec![AttemptStatus::Completed]);
+    }
+
+    #[test]
+    fn examiner_abandon() {
+        let database = TDatabase::default();
+        let config = TConfig {
+            allow_parallel_tests: true,
+            window: Default::default(),
+        };
+        let mut examiner = Examiner::new(Box::new(config), Box::new(database.clone()));
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let physics = "physics".to_string();
+
+        // Отказ от попытки теста math запрещен настройками.
+        let math_session = start_test_session(&mut examiner, "math");
+        examiner.next_question(&student, &math, &math_session);
+        let resp = examiner.abandon_test(&student, &math, &math_session);
+        assert_eq!(resp, Response::AbandonNotAllowed);
+
+        let physics_session = start_test_session(&mut examiner, "physics");
+        examiner.next_question(&student, &physics, &physics_session);
+        let resp = examiner.abandon_test(&student, &physics, &physics_session);
+        assert!(matches!(resp, Response::End { .. }));
+        let resp = examiner.finish_test(&student, &math, &math_session);
+        assert!(matches!(resp, Response::End { .. }));
+
+        assert_eq!(
+            *database.statuses.borrow(),
+            vec![AttemptStatus::Abandoned, AttemptStatus::Finished]
+        );
+        assert!(database.open_variants.borrow().is_empty());
+    }
+
+    #[test]
+    fn examiner_put_answer_without_question() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let session = start_session(&mut examiner);
+
+        // Попытка начата, но ни один вопрос еще не показан.
+        examiner.answers_state(&student, &math, &session);
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+
+        // Ответ не принят, вместо этого показывается вопрос и начинается отсчет времени.
+        assert!(matches!(
+            resp,
+            Response::NextQuestion {
+                index: 0,
+                answer: None,
+                ..
+            }
+        ));
+        let saved = database.open_variants.borrow()[&(student.clone(), math.clone())].clone();
+        assert_eq!(saved.answers, vec![None]);
+        assert!(saved.shown_at[0].is_some());
+
+        let resp = examiner.put_answer(&student, &math, &Answer::new(vec![0]), &session);
+        assert!(matches!(resp, Response::End { .. }));
+    }
+
+    #[test]
+    fn examiner_retry_cooldown() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let student = "student".to_string();
+        let physics = "physics".to_string();
+
+        // Для теста physics между попытками должно пройти 10 минут.
+        let session = start_test_session(&mut examiner, "physics");
+        examiner.next_question(&student, &physics, &session);
+        let resp = examiner.abandon_test(&student, &physics, &session);
+        assert!(matches!(resp, Response::End { .. }));
+
+        let resp = examiner.banner_to_start_test(&student, &physics);
+        let Response::AttemptCooldown { remaining_seconds } = resp else {
+            panic!("Ожидалось ограничение частоты попыток: {resp:?}");
+        };
+        assert!((590..=600).contains(&remaining_seconds));
+
+        // Через 10 минут после окончания попытки тест снова доступен.
+        let end = chrono::Local::now() - chrono::Duration::minutes(11);
+        database
+            .last_end
+            .borrow_mut()
+            .insert(physics.clone(), end.fixed_offset());
+        start_test_session(&mut examiner, "physics");
+    }
+
+    #[test]
+    fn examiner_availability_window() {
+        let window = Rc::new(Cell::new((None, None)));
+        let config = TConfig {
+            allow_parallel_tests: false,
+            window: window.clone(),
+        };
+        let mut examiner = Examiner::new(Box::new(config), Box::new(TDatabase::default()));
+        let student = "student".to_string();
+        let math = "math".to_string();
+        let now = chrono::Local::now().naive_local();
+
+        // Тест еще не открыт.
+        window.set((Some(now + chrono::Duration::hours(1)), None));
+        let resp = examiner.banner_to_start_test(&student, &math);
+        let Response::TestUnavailable { availability } = resp else {
+            panic!("Ожидалось окно доступности теста: {resp:?}");
+        };
+        assert_eq!(availability.state, WindowState::Upcoming);
+        let Response::AvaliableTests { tests } = examiner.avaliable_tests(&student) else {
+            panic!("Ожидался список тестов");
+        };
+        assert_eq!(tests[0].2, availability);
+
+        // Начатая попытка завершается при закрытии теста.
+        window.set((Some(now - chrono::Duration::hours(1)), None));
+        let session = start_session(&mut examiner);
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::NextQuestion { .. }));
+
+        window.set((
+            Some(now - chrono::Duration::hours(1)),
+            Some(now - chrono::Duration::minutes(1)),
+        ));
+        let resp = examiner.next_question(&student, &math, &session);
+        assert!(matches!(resp, Response::End { .. }));
+
+        let resp = examiner.banner_to_start_test(&student, &math);
+        let Response::TestUnavailable { availability } = resp else {
+            panic!("Ожидалось окно доступности теста: {resp:?}");
+        };
+        assert_eq!(availability.state, WindowState::Closed);
+    }
+
+    #[test]
+    fn examiner_shutdown_finish() {
+        let database = TDatabase::default();
+        let mut examiner = examiner_with_database(database.clone());
+        let session = start_session(&mut examiner);
+        examiner.next_question(&"student".to_string(), &"math".to_string(), &session);
+
+        examiner.shutdown(ShutdownPolicy::Finish);
+        assert_eq!(database.finished.borrow().len(), 1);
+        assert_eq!(
+            *database.statuses.borrow(),
+            vec![AttemptStatus::Interrupted]
+        );
+        assert!(database.open_variants.borrow().is_empty());
+    }
+
+    #[test]
+    fn shuffled_answers() {
+        let question = Question {
+            question: "Что из перечисленного - числа?".to_string(),
+            answers: ["1", "2", "a", "b", "Ничего из перечисленного"]
+                .map(String::from)
+                .to_vec(),
+            correct_answer: Answer::new(vec![0, 1]),
+            time_limit_seconds: None,
+            pinned_answers: vec![4],
+            answer_order: vec![],
+            weight: 1.0,
+            scoring: None,
+        };
+
+        for seed in 0..20 {
+            let shuffled = shuffle_answers(question.clone(), &mut ChaCha8Rng::seed_from_u64(seed));
+            assert_eq!(shuffled.answer_order[4], 4);
+            for (position, &source) in shuffled.answer_order.iter().enumerate() {
+                assert_eq!(shuffled.answers[position], question.answers[source]);
+            }
+            let correct: Vec<&String> = shuffled
+                .correct_answer
+                .as_array()
+                .iter()
+                .map(|&i| &shuffled.answers[i])
+                .collect();
+            assert_eq!(correct.len(), 2);
+            assert!(correct.iter().all(|a| *a == "1" || *a == "2"));
+        }
+
+        // Порядок зависит только от зерна.
+        let first = shuffle_answers(question.clone(), &mut ChaCha8Rng::seed_from_u64(7));
+        let second = shuffle_answers(question, &mut ChaCha8Rng::seed_from_u64(7));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn sections_draw() {
+        let section = |name: &str, questions: Vec<usize>| Section {
+            name: name.to_string(),
+            questions,
+        };
+        let sections = vec![
+            section("Сложение", vec![0, 1, 2]),
+            section("Умножение", vec![3, 4]),
+            section("Деление", vec![5]),
+        ];
+        let section_settings = |name: &str, questions_number: usize| SectionSettings {
+            name: name.to_string(),
+            questions_number,
+        };
+        let mut test_settings = settings::TestSettings {
+            sections: vec![
+                section_settings("Сложение", 2),
+                section_settings("Умножение", 1),
+            ],
+            fixed_question_order: true,
+            ..Default::default()
+        };
+
+        for seed in 0..20 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let questions = draw_sections(sections.clone(), &test_settings, &mut rng);
+            // Из раздела без настроек выбираются все вопросы.
+            assert_eq!(questions.len(), 4);
+            assert!(questions[0] < questions[1] && questions[1] <= 2);
+            assert!((3..=4).contains(&questions[2]));
+            assert_eq!(questions[3], 5);
+        }
+
+        test_settings.shuffle_sections = true;
+        let orders: HashSet<usize> = (0..20)
+            .map(|seed| {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                draw_sections(sections.clone(), &test_settings, &mut rng)[0]
+            })
+            .collect();
+        assert!(orders.contains(&5));
+    }
+
+    #[test]
+    fn weighted_mark() {
+        let mut variant = two_questions_variant();
+        variant.questions[1].weight = 3.0;
+        variant.answers = vec![Some(Answer::new(vec![1])), Some(Answer::new(vec![1]))];
+
+        let mark = calculate_mark(&variant);
+        assert_eq!(mark, Mark::new(3.0, 4.0));
+        assert_eq!(mark.percent(), Some(75.0));
+
+        variant.questions.clear();
+        assert_eq!(calculate_mark(&variant).percent(), None);
+    }
+
+    #[test]
+    fn grade_scale() {
+        let grade = |label: &str, min_percent| GradeSettings {
+            label: label.to_string(),
+            min_percent,
+        };
+        let settings = settings::TestSettings {
+            grades: vec![
+                grade("5", 85.0),
+                grade("2", 0.0),
+                grade("4", 70.0),
+                grade("3", 50.0),
+            ],
+            pass_percent: Some(50.0),
+            ..Default::default()
+        };
+
+        assert_eq!(settings.grade(100.0), Some("5".to_string()));
+        // 7 / 10 в f32 чуть меньше 0.7, граница все равно достигнута.
+        assert_eq!(settings.grade(7.0 / 10.0 * 100.0), Some("4".to_string()));
+        assert_eq!(settings.grade(49.9), Some("2".to_string()));
+        assert_eq!(settings.passed(50.0), Some(true));
+        assert_eq!(settings.passed(49.9), Some(false));
+
+        // Без шкалы и проходного процента оценка не выставляется.
+        let settings = settings::TestSettings::default();
+        assert_eq!(settings.grade(100.0), None);
+        assert_eq!(settings.passed(100.0), None);
+    }
+
+    #[test]
+    fn official_result_policy() {
+        let mark = Mark::new;
+        // Лучшая попытка выбирается по проценту, а не по баллам.
+        let marks = vec![mark(2.0, 4.0), mark(5.0, 5.0), mark(6.0, 10.0)];
+        let official = |official_result| {
+            settings::TestSettings {
+                official_result,
+                pass_percent: Some(60.0),
+                ..Default::default()
+            }
+            .official_mark(&marks)
+        };
+
+        assert_eq!(official(ResultPolicy::Best), Some(mark(5.0, 5.0)));
+        assert_eq!(official(ResultPolicy::First), Some(mark(2.0, 4.0)));
+        assert_eq!(official(ResultPolicy::Last), Some(mark(6.0, 10.0)));
+        assert_eq!(
+            official(ResultPolicy::Mean),
+            Some(Mark {
+                passed: Some(true),
+                ..mark(13.0 / 3.0, 19.0 / 3.0)
+            })
+        );
+        assert_eq!(settings::TestSettings::default().official_mark(&[]), None);
+    }
+
+    #[test]
+    fn scoring_strategies() {
+        let mut question = Question {
+            question: "Какие числа четные?".to_string(),
+            answers: ["1", "2", "3", "4", "5"].map(String::from).to_vec(),
+            correct_answer: Answer::new(vec![1, 3]),
+            time_limit_seconds: None,
+            pinned_answers: vec![],
+            answer_order: vec![],
+            weight: 1.0,
+            scoring: None,
+        };
+        let score = |question: &Question, answer: Vec<usize>| {
+            question_score(&Answer::new(answer), question)
+        };
+
+        // Без правила в вопросе используется правило по умолчанию.
+        assert_eq!(score(&question, vec![1, 2]), 0.0);
+        assert_eq!(score(&question, vec![1]), 0.5);
+
+        question.scoring = Some(Scoring::AllOrNothing);
+        assert_eq!(score(&question, vec![1]), 0.0);
+        assert_eq!(score(&question, vec![1, 3]), 1.0);
+
+        question.scoring = Some(Scoring::NoPenalty);
+        assert_eq!(score(&question, vec![1, 2]), 0.5);
+        assert_eq!(score(&question, vec![0, 1, 2, 3, 4]), 1.0);
+
+        // Неправильный ответ отнимает 1 / 3: неправильных вариантов три.
+        question.scoring = Some(Scoring::Negative);
+        assert_eq!(score(&question, vec![1, 3]), 1.0);
+        assert!((score(&question, vec![1, 2]) - (0.5 - 1.0 / 3.0)).abs() < 1e-6);
+        assert_eq!(score(&question, vec![0, 2, 4]), -1.0);
+
+        question.correct_answer = Answer::new(vec![1]);
+        assert_eq!(score(&question, vec![0]), -0.25);
+    }
+
+    #[test]
+    fn duplicate_and_out_of_range_answers() {
+        assert_eq!(Answer::new(vec![3, 1, 1, 3]).as_array(), vec![1, 3]);
+        let mut answer = Answer::new(vec![1]);
+        answer.push(1);
+        assert_eq!(answer, Answer::new(vec![1]));
+
+        let mut question = Question {
+            question: "Какие числа четные?".to_string(),
+            answers: ["1", "2", "3", "4", "5"].map(String::from).to_vec(),
+            correct_answer: Answer::new(vec![1, 3]),
+            time_limit_seconds: None,
+            pinned_answers: vec![],
+            answer_order: vec![],
+            weight: 2.0,
+            scoring: Some(Scoring::NoPenalty),
+        };
+        let score = |question: &Question, answer: Vec<usize>| {
+            question_score(&Answer::new(answer), question)
+        };
+
+        // Повторы не увеличивают балл, несуществующие ответы не учитываются.
+        assert_eq!(score(&question, vec![1, 1, 1, 1]), 0.5);
+        assert_eq!(score(&question, vec![1, 3, 3, 7, 9]), 1.0);
+        question.scoring = Some(Scoring::Negative);
+        assert_eq!(score(&question, vec![1, 3, 7]), 1.0);
+
+        let mut variant = two_questions_variant();
+        variant.questions = vec![question];
+        variant.answers = vec![Some(Answer::new(vec![1, 1, 3, 3, 3, 8]))];
+        let mark = calculate_mark(&variant);
+        assert!(mark.points <= mark.max_points);
     }
 
     #[test]
@@ -3,8 +3,8 @@ pub mod network;
 pub mod schema;
 pub mod settings;
 
-use schema::{Answer, Question, Variant};
-use settings::{Settings, TestSettings};
+use schema::{Answer, AttemptStatus, Mark, Question, Variant};
+use settings::{Section, Settings, TestSettings};
 
 /// Интерфейс взаимодействия Экзаменатора с настройками.
 pub trait Config {
@@ -26,6 +26,12 @@ pub trait Config {
     /// Получить количество вопросов в тесте.
     fn questions_count(&self, testname: &String) -> Option<usize>;
 
+    /// Получить версию теста testname.
+    fn test_version(&self, testname: &String) -> Option<String>;
+
+    /// Получить разделы теста testname.
+    fn sections(&self, testname: &String) -> Option<Vec<Section>>;
+
     /// Получить ответы на вопрос question_id теста testname.
     fn answer(&self, testname: &String, question_id: usize) -> Option<Answer>;
 
@@ -44,17 +50,37 @@ pub trait Database {
     /// Сколько попыток для прохождения теста testname потратил пользователь username.
     fn attempts_counter(&mut self, username: &String, testname: &String) -> u32;
 
+    /// Время окончания последней попытки, включая попытки с состоянием Abandoned.
+    /// Попытки с состоянием Interrupted не учитываются.
+    fn last_attempt_end(
+        &mut self,
+        username: &String,
+        testname: &String,
+    ) -> Option<chrono::DateTime<chrono::FixedOffset>>;
+
     /// Получить баллы за тест testname для пользователя username.
-    fn marks(&mut self, username: &String, testname: &String) -> Vec<f32>;
+    fn marks(&mut self, username: &String, testname: &String) -> Vec<Mark>;
 
     /// Сохранить баллы за тест testname для пользователя username.
+    /// Попытки с состоянием Abandoned не учитываются в attempts_counter и marks.
+    #[allow(clippy::too_many_arguments)]
     fn append_mark(
         &mut self,
         username: &String,
         testname: &String,
-        mark: f32,
+        mark: Mark,
         start_timestamp: &String,
         end_timestamp: &String,
         variant: &Variant,
+        status: AttemptStatus,
     );
+
+    /// Сохранить незавершенную попытку, заменив ранее сохраненную.
+    fn save_open_variant(&mut self, variant: &Variant);
+
+    /// Удалить сохраненную незавершенную попытку.
+    fn remove_open_variant(&mut self, username: &String, testname: &String);
+
+    /// Загрузить сохраненные незавершенные попытки.
+    fn load_open_variants(&mut self) -> Vec<Variant>;
 }
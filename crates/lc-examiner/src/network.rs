@@ -1,17 +1,44 @@
 /// Структуры сетевой коммуникации между сервером и клиеном
 ///
 /// Порядок обмена.
+/// Проверка совместимости (при запуске клиента):
+/// -> Request:Hello (версия протокола клиента)
+/// <- Response:Welcome (версия и возможности сервера)
+/// (Или <- Response:IncompatibleVersion если версии протокола не совпадают)
+///
 /// Выполнение теста:
 /// -> Request:StartTest
-/// <- Response:TestStarted (Проверяются учетные данные, выдается приветствие)
+/// <- Response:TestStarted (Проверяются учетные данные, выдается приветствие
+///                          и токен сессии)
 /// (Или <- Response:End (Строка заключения) если тест уже пройден)
-/// -> Request:GetNextQuestion
-/// <- Response:NextQuestion (Фиксируется время начала теста, выдается вопрос)
-/// -> Response:PutAnswer
+/// (Или <- Response:TestUnavailable (окно доступности теста) если тест
+///        еще не открыт или уже закрыт)
+/// (Или <- Response:AttemptCooldown (время до следующей попытки) если
+///        с окончания предыдущей попытки прошло мало времени)
+/// -> Request:GetNextQuestion (с токеном сессии)
+/// <- Response:NextQuestion (Фиксируется время начала теста, выдается вопрос,
+///                          его номер, количество вопросов и оставшееся время)
+/// -> Response:PutAnswer (с токеном сессии)
 /// <- Response:Ok (Подтверждение принятия вопроса)
-/// -> Request:NextQuestion
+/// -> Request:NextQuestion (с токеном сессии)
 /// <- Response:End (Выдается строка заключения)
 ///
+/// Свободный порядок ответов (после Request:StartTest, с токеном сессии):
+/// -> Request:GetAnswersState
+/// <- Response:AnswersState (Фиксируется время начала теста, выдается перечень
+///                           отвеченных вопросов)
+/// -> Request:GetQuestion (номер вопроса)
+/// <- Response:NextQuestion (Вопрос и ранее данный ответ)
+/// -> Request:SetAnswer (номер вопроса и ответ)
+/// <- Response:Ok
+/// (Или <- Response:QuestionTimeOver если время ответа на вопрос истекло)
+/// -> Request:FinishTest
+/// <- Response:End (Вопросы без ответа оцениваются нулем)
+/// -> Request:AbandonTest
+/// <- Response:End (Попытка не учитывается)
+/// (Или <- Response:AbandonNotAllowed если отказ от попытки запрещен)
+/// (Или <- Response:ResponseError если вопроса с таким номером нет)
+///
 /// Получение списка тестов:
 /// -> Request:GetAvaliableTests,
 /// <- Response:AvaliableTests (список тестов, доступных пользователю с указанием
@@ -26,15 +53,41 @@
 ///
 /// -> Request (любой запрос)
 /// <- Response:ResponseError - некорректный запрос
+///
+/// -> Request:GetNextQuestion или Request:PutAnswer
+/// <- Response:InvalidSession - токен сессии не соответствует попытке
+///
+/// -> Request:StartTest или Request:GetNextQuestion
+/// <- Response:OtherTestInProgress - у пользователя не завершен другой тест,
+///                                   а параллельное прохождение запрещено
+///
+/// -> Request (любой запрос)
+/// <- Response:TooManyRequests - превышено ограничение частоты запросов
 use serde::{Deserialize, Serialize};
 
-use crate::schema::Answer;
+use crate::schema::{Answer, Availability, Mark};
+
+/// Версия протокола обмена. Увеличивается при несовместимых изменениях
+/// структур Request и Response.
+///
+/// 2 - NextQuestion содержит номер вопроса, количество вопросов и оставшееся время.
+/// 3 - команды GetQuestion, SetAnswer, GetAnswersState, FinishTest и AbandonTest;
+///     в NextQuestion добавлены время на вопрос и данный ранее ответ;
+///     ответы AbandonNotAllowed, QuestionTimeOver, AttemptCooldown и TestUnavailable;
+///     Marks содержит Mark с максимальным баллом, оценкой и признаком сдачи,
+///     а также итоговый результат; AvaliableTests содержит окно доступности теста.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Возможности сервера, сообщаемые клиенту при проверке совместимости.
+pub const SERVER_CAPABILITIES: &[&str] = &["sessions", "navigation"];
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Request {
     pub user: String,
     pub test: String,
     pub command: Command,
+    /// Токен сессии, выданный при запуске теста.
+    pub session: Option<String>,
 }
 
 impl Request {
@@ -43,42 +96,128 @@ impl Request {
             user: user.as_ref().to_string(),
             test: test.as_ref().to_string(),
             command,
+            session: None,
         }
     }
+
+    /// Приложить к запросу токен сессии.
+    pub fn with_session<S: AsRef<str>>(mut self, session: S) -> Request {
+        self.session = Some(session.as_ref().to_string());
+        self
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum Command {
+    /// Должен оставаться первым вариантом, чтобы его номер не менялся
+    /// между версиями протокола.
+    Hello {
+        client_version: u32,
+        capabilities: Vec<String>,
+    },
     GetAvaliableTests,
     StartTest,
     GetNextQuestion,
-    PutAnswer { answer: Answer },
+    /// Ответ на последний показанный вопрос. Если вопрос еще не показывался,
+    /// ответ не сохраняется, а сервер присылает вопрос.
+    PutAnswer {
+        answer: Answer,
+    },
+    /// Получить вопрос с номером index, начиная с нуля.
+    GetQuestion {
+        index: usize,
+    },
+    /// Ответить на вопрос с номером index или изменить ответ.
+    /// Пустой ответ отменяет ранее данный ответ.
+    SetAnswer {
+        index: usize,
+        answer: Answer,
+    },
+    /// Получить перечень отвеченных вопросов.
+    GetAnswersState,
+    /// Завершить тест.
+    FinishTest,
+    /// Отказаться от попытки, если это разрешено настройками теста.
+    AbandonTest,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum Marks {
-    Marks { marks: Vec<f32> },
+    Marks {
+        marks: Vec<Mark>,
+        /// Итоговый результат по правилу official_result настроек теста.
+        #[serde(default)]
+        official: Option<Mark>,
+    },
     Done,
     Empty,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum Response {
+    /// Ответы на приветствие располагаются первыми по той же причине, что и Command::Hello.
+    Welcome {
+        server_version: u32,
+        capabilities: Vec<String>,
+    },
+    IncompatibleVersion {
+        server_version: u32,
+    },
     AvaliableTests {
-        tests: Vec<(String, Marks)>,
-    }, // Название теста и результат
+        tests: Vec<(String, Marks, Availability)>,
+    }, // Название теста, результат и окно доступности
     TestStarted {
         banner: String,
+        session: String,
     },
     NextQuestion {
         question: String,
         answers: Vec<String>,
+        /// Номер вопроса в варианте, начиная с нуля.
+        index: usize,
+        /// Количество вопросов в варианте.
+        total: usize,
+        /// Время до окончания теста в секундах.
+        remaining_seconds: u64,
+        /// Время до окончания ответа на вопрос в секундах, если оно ограничено.
+        question_remaining_seconds: Option<u64>,
+        /// Вопрос имеет несколько правильных ответов.
+        multiple_choice: bool,
+        /// Ранее данный ответ на вопрос.
+        answer: Option<Answer>,
+        session: String,
     },
     Ok,
     End {
         marks: Marks,
     },
     NotAllowedUser,
+    InvalidSession,
     ServerError,
     ResponseError,
+    /// Превышено ограничение частоты запросов, запрос нужно повторить позже.
+    TooManyRequests,
+    /// У пользователя не завершен тест testname, начать другой тест нельзя.
+    OtherTestInProgress {
+        testname: String,
+    },
+    /// Отвечен ли каждый вопрос варианта, время до окончания теста в секундах
+    /// и истекло ли время ответа на вопрос.
+    AnswersState {
+        answered: Vec<bool>,
+        closed: Vec<bool>,
+        remaining_seconds: u64,
+    },
+    /// Настройки теста не разрешают отказ от попытки.
+    AbandonNotAllowed,
+    /// Время ответа на вопрос истекло, ответ не сохранен.
+    QuestionTimeOver,
+    /// Следующая попытка станет доступна через remaining_seconds секунд.
+    AttemptCooldown {
+        remaining_seconds: u64,
+    },
+    /// Тест еще не открыт или уже закрыт.
+    TestUnavailable {
+        availability: Availability,
+    },
 }
@@ -1,20 +1,49 @@
 use serde::{Deserialize, Serialize};
 
+/// Номера выбранных ответов по возрастанию, без повторов.
 #[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
+#[serde(from = "RawAnswer")]
 pub struct Answer {
     answers: Vec<usize>,
 }
 
+/// Ответ в том виде, в котором его прислал клиент.
+#[derive(Deserialize)]
+struct RawAnswer {
+    answers: Vec<usize>,
+}
+
+impl From<RawAnswer> for Answer {
+    fn from(raw: RawAnswer) -> Answer {
+        Answer::new(raw.answers)
+    }
+}
+
 impl Answer {
     pub fn new(answer: Vec<usize>) -> Answer {
         let mut answers = answer.clone();
         answers.sort();
+        answers.dedup();
         Answer { answers }
     }
 
     pub fn push(&mut self, answer: usize) {
-        self.answers.push(answer);
-        self.answers.sort();
+        if !self.answers.contains(&answer) {
+            self.answers.push(answer);
+            self.answers.sort();
+        }
+    }
+
+    /// Ответ без номеров, которых нет среди answers_count вариантов ответа.
+    pub fn in_range(&self, answers_count: usize) -> Answer {
+        Answer {
+            answers: self
+                .answers
+                .iter()
+                .copied()
+                .filter(|&answer| answer < answers_count)
+                .collect(),
+        }
     }
 
     pub fn as_array(&self) -> Vec<usize> {
@@ -27,6 +56,121 @@ pub struct Question {
     pub question: String,
     pub answers: Vec<String>,
     pub correct_answer: Answer,
+    /// Время на ответ в секундах, заменяет ограничение из настроек теста.
+    #[serde(default)]
+    pub time_limit_seconds: Option<u64>,
+    /// Номера ответов, которые не перемешиваются и остаются на своих местах.
+    #[serde(default)]
+    pub pinned_answers: Vec<usize>,
+    /// Номер в файле теста для каждого ответа варианта. Пустой - ответы не перемешаны.
+    #[serde(default)]
+    pub answer_order: Vec<usize>,
+    /// Вес вопроса: балл за полностью правильный ответ.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Правило оценивания вопроса, заменяет правило из настроек теста.
+    #[serde(default)]
+    pub scoring: Option<Scoring>,
+}
+
+/// Правило оценивания ответа на вопрос.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Scoring {
+    /// За правильный ответ `1 / n_true` баллов, неправильный ответ отнимает столько же,
+    /// балл за вопрос от 0 до 1.
+    #[default]
+    Partial,
+    /// 1 балл только за полностью правильный ответ.
+    AllOrNothing,
+    /// За правильный ответ `1 / n_true` баллов, неправильные ответы не учитываются.
+    NoPenalty,
+    /// За правильный ответ `1 / n_true` баллов, неправильный отнимает `1 / n_false`,
+    /// где `n_false` - количество неправильных вариантов. Балл за вопрос от -1 до 1.
+    Negative,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Результат попытки: набранные баллы и максимально возможные баллы варианта.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Mark {
+    pub points: f32,
+    pub max_points: f32,
+    /// Оценка по шкале теста, если шкала задана.
+    #[serde(default)]
+    pub grade: Option<String>,
+    /// Сдан ли тест, если задан проходной процент.
+    #[serde(default)]
+    pub passed: Option<bool>,
+}
+
+impl Mark {
+    /// Результат без оценки и отметки о сдаче теста.
+    pub fn new(points: f32, max_points: f32) -> Mark {
+        Mark {
+            points,
+            max_points,
+            grade: None,
+            passed: None,
+        }
+    }
+
+    /// Доля набранных баллов в процентах. None, если максимальный балл неизвестен.
+    pub fn percent(&self) -> Option<f32> {
+        (self.max_points > 0.0).then(|| self.points / self.max_points * 100.0)
+    }
+}
+
+/// Состояние окна доступности теста.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowState {
+    /// Тест еще не открыт.
+    Upcoming,
+    Open,
+    /// Тест закрыт, начатые попытки завершены.
+    Closed,
+}
+
+/// Окно доступности теста по времени сервера и его состояние.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Availability {
+    pub open_at: Option<chrono::NaiveDateTime>,
+    pub close_at: Option<chrono::NaiveDateTime>,
+    pub state: WindowState,
+}
+
+/// Чем закончилась попытка.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttemptStatus {
+    /// Пользователь ответил на все вопросы.
+    Completed,
+    /// Тест завершен досрочно пользователем.
+    Finished,
+    /// Истекло время теста.
+    Expired,
+    /// Пользователь отказался от попытки, она не учитывается.
+    Abandoned,
+    /// Попытка завершена сервером при остановке. Оценивается как завершенная
+    /// досрочно, но не начинает перерыв между попытками.
+    Interrupted,
+}
+
+impl AttemptStatus {
+    /// Название состояния для хранения в базе данных и отчетах.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttemptStatus::Completed => "completed",
+            AttemptStatus::Finished => "finished",
+            AttemptStatus::Expired => "expired",
+            AttemptStatus::Abandoned => "abandoned",
+            AttemptStatus::Interrupted => "interrupted",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -35,5 +179,31 @@ pub struct Variant {
     pub testname: String,
     pub start_timestamp: chrono::DateTime<chrono::Local>,
     pub questions: Vec<Question>,
-    pub answers: Vec<Answer>,
+    /// Ответы на вопросы варианта, None - вопрос без ответа.
+    pub answers: Vec<Option<Answer>>,
+    /// Время первого показа каждого вопроса, от него отсчитывается время на ответ.
+    #[serde(default)]
+    pub shown_at: Vec<Option<chrono::DateTime<chrono::Local>>>,
+    /// Зерно генератора, по которому выбраны вопросы варианта.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Версия теста, из которого выбраны вопросы варианта.
+    #[serde(default)]
+    pub test_version: Option<String>,
+}
+
+impl Variant {
+    /// Попытка без вопросов, начатая сейчас.
+    pub fn new(username: &str, testname: &str) -> Variant {
+        Variant {
+            username: username.to_string(),
+            testname: testname.to_string(),
+            start_timestamp: chrono::Local::now(),
+            questions: vec![],
+            answers: vec![],
+            shown_at: vec![],
+            seed: None,
+            test_version: None,
+        }
+    }
 }
@@ -1,10 +1,22 @@
-use crate::schema::Question;
-use serde::{Deserialize, Serialize};
+use crate::schema::{Availability, Mark, Question, Scoring, WindowState};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Раздел теста: название и номера вопросов раздела.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Section {
+    pub name: String,
+    pub questions: Vec<usize>,
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Test {
     pub banner: String,
     pub questions: Vec<Question>,
+    /// Разделы теста в порядке файла. Тест без разделов состоит из одного раздела без названия.
+    pub sections: Vec<Section>,
+    /// Версия теста: начало отпечатка SHA-256 файла теста.
+    pub version: String,
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
@@ -19,9 +31,17 @@ pub struct TestSettings {
     #[serde(default)]
     pub test_duration_minutes: i64,
 
+    /// Время на ответ на каждый вопрос в секундах. 0 - без ограничения.
+    #[serde(default)]
+    pub question_duration_seconds: u64,
+
     #[serde(default)]
     pub number_of_attempts: u32,
 
+    /// Время между окончанием попытки и началом следующей. 0 - без ограничения.
+    #[serde(default)]
+    pub retry_cooldown_minutes: i64,
+
     /// Castumization
     #[serde(default)]
     pub show_results: bool,
@@ -31,6 +51,166 @@ pub struct TestSettings {
 
     #[serde(default)]
     pub allowed_users_path: Option<String>,
+
+    /// Разрешить отказ от попытки без ее учета.
+    #[serde(default)]
+    pub allow_abandon: bool,
+
+    /// Перемешивать ответы на вопросы в каждом варианте.
+    #[serde(default)]
+    pub shuffle_answers: bool,
+
+    /// Правило оценивания ответов.
+    #[serde(default)]
+    pub scoring: Scoring,
+
+    /// Количество вопросов, выбираемых из каждого раздела.
+    /// Если указано, заменяет questions_number.
+    #[serde(default)]
+    #[serde(rename = "section")]
+    pub sections: Vec<SectionSettings>,
+
+    /// Вопросы варианта идут в порядке файла теста, иначе перемешиваются.
+    #[serde(default)]
+    pub fixed_question_order: bool,
+
+    /// Перемешивать разделы, иначе разделы идут в порядке файла теста.
+    #[serde(default)]
+    pub shuffle_sections: bool,
+
+    /// Шкала оценок: нижние границы процента набранных баллов.
+    #[serde(default)]
+    #[serde(rename = "grade")]
+    pub grades: Vec<GradeSettings>,
+
+    /// Процент набранных баллов, необходимый для сдачи теста.
+    #[serde(default)]
+    pub pass_percent: Option<f32>,
+
+    /// Какая из попыток считается итоговым результатом.
+    #[serde(default)]
+    pub official_result: ResultPolicy,
+
+    /// Время открытия и закрытия теста по времени сервера.
+    /// При закрытии теста начатые попытки завершаются.
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub open_at: Option<NaiveDateTime>,
+
+    #[serde(default, deserialize_with = "deserialize_datetime")]
+    pub close_at: Option<NaiveDateTime>,
+}
+
+/// Выбор итогового результата из результатов попыток.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultPolicy {
+    /// Лучшая попытка по проценту набранных баллов.
+    #[default]
+    Best,
+    /// Последняя попытка.
+    Last,
+    /// Первая попытка.
+    First,
+    /// Среднее значение баллов по всем попыткам.
+    Mean,
+}
+
+/// Оценка и наименьший процент набранных баллов, с которого она выставляется.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct GradeSettings {
+    pub label: String,
+    pub min_percent: f32,
+}
+
+/// Допуск при сравнении процента с границами: 7 / 10 * 100 в f32 чуть меньше 70.
+const PERCENT_EPSILON: f32 = 1e-3;
+
+/// Количество вопросов, выбираемых из раздела теста.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+pub struct SectionSettings {
+    /// Название раздела - текст заголовка раздела в файле теста.
+    pub name: String,
+
+    /// 0 - все вопросы раздела.
+    #[serde(default)]
+    pub questions_number: usize,
+}
+
+impl TestSettings {
+    /// Количество вопросов, выбираемых из раздела section.
+    /// Разделы, не указанные в настройках, включаются в вариант целиком.
+    pub fn section_questions_number(&self, section: &str) -> usize {
+        self.sections
+            .iter()
+            .find(|settings| settings.name == section)
+            .map_or(0, |settings| settings.questions_number)
+    }
+
+    /// Оценка за попытку, в которой набрано percent процентов баллов.
+    /// Выбирается оценка с наибольшей границей, не превышающей percent.
+    pub fn grade(&self, percent: f32) -> Option<String> {
+        self.grades
+            .iter()
+            .filter(|grade| percent + PERCENT_EPSILON >= grade.min_percent)
+            .max_by(|a, b| a.min_percent.total_cmp(&b.min_percent))
+            .map(|grade| grade.label.clone())
+    }
+
+    /// Сдан ли тест. None, если проходной процент не задан.
+    pub fn passed(&self, percent: f32) -> Option<bool> {
+        self.pass_percent
+            .map(|pass_percent| percent + PERCENT_EPSILON >= pass_percent)
+    }
+
+    /// Окно доступности теста и его состояние в момент now.
+    pub fn availability(&self, now: NaiveDateTime) -> Availability {
+        let state = if self.open_at.is_some_and(|open_at| now < open_at) {
+            WindowState::Upcoming
+        } else if self.close_at.is_some_and(|close_at| now >= close_at) {
+            WindowState::Closed
+        } else {
+            WindowState::Open
+        };
+        Availability {
+            open_at: self.open_at,
+            close_at: self.close_at,
+            state,
+        }
+    }
+
+    /// Итоговый результат по результатам попыток marks в порядке их завершения.
+    /// Для среднего значения оценка и признак сдачи вычисляются заново.
+    pub fn official_mark(&self, marks: &[Mark]) -> Option<Mark> {
+        match self.official_result {
+            ResultPolicy::Best => marks
+                .iter()
+                .max_by(|a, b| {
+                    let a = a.percent().unwrap_or(a.points);
+                    let b = b.percent().unwrap_or(b.points);
+                    a.total_cmp(&b)
+                })
+                .cloned(),
+            ResultPolicy::Last => marks.last().cloned(),
+            ResultPolicy::First => marks.first().cloned(),
+            ResultPolicy::Mean => {
+                if marks.is_empty() {
+                    return None;
+                }
+                let count = marks.len() as f32;
+                let mut mark = Mark {
+                    points: marks.iter().map(|mark| mark.points).sum::<f32>() / count,
+                    max_points: marks.iter().map(|mark| mark.max_points).sum::<f32>() / count,
+                    grade: None,
+                    passed: None,
+                };
+                if let Some(percent) = mark.percent() {
+                    mark.grade = self.grade(percent);
+                    mark.passed = self.passed(percent);
+                }
+                Some(mark)
+            }
+        }
+    }
 }
 
 impl std::default::Default for TestSettings {
@@ -39,14 +219,38 @@ impl std::default::Default for TestSettings {
             caption: "".to_string(),
             questions_number: 0,
             test_duration_minutes: 0,
+            question_duration_seconds: 0,
             show_results: true,
             allowed_users: Some(vec![]),
             allowed_users_path: None,
             number_of_attempts: 1,
+            retry_cooldown_minutes: 0,
+            allow_abandon: false,
+            shuffle_answers: false,
+            scoring: Scoring::default(),
+            sections: vec![],
+            fixed_question_order: false,
+            shuffle_sections: false,
+            grades: vec![],
+            pass_percent: None,
+            official_result: ResultPolicy::default(),
+            open_at: None,
+            close_at: None,
         }
     }
 }
 
+/// Обработка незавершенных попыток при остановке сервера.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownPolicy {
+    /// Завершить попытки и выставить оценки по уже данным ответам.
+    Finish,
+    /// Сохранить попытки, чтобы студенты продолжили их после запуска сервера.
+    #[default]
+    Checkpoint,
+}
+
 #[derive(Deserialize, Debug, Clone, PartialEq, Serialize)]
 pub struct Settings {
     #[serde(default)]
@@ -58,6 +262,65 @@ pub struct Settings {
     #[serde(default)]
     pub server_address: String,
 
+    /// Путь к Unix сокету. Если указан, сервер принимает запросы через сокет
+    /// и определяет пользователя средствами операционной системы.
+    #[serde(default)]
+    pub socket_path: String,
+
+    /// Адрес HTTP сервера, принимающего запросы в формате JSON.
+    /// Если не указан, HTTP сервер не запускается.
+    #[serde(default)]
+    pub http_address: String,
+
+    /// Пути к сертификату и закрытому ключу сервера в формате PEM.
+    /// Если указаны, соединения по TCP и HTTP защищаются TLS.
+    #[serde(default)]
+    pub tls_cert_path: String,
+
+    #[serde(default)]
+    pub tls_key_path: String,
+
+    /// Разрешить незашифрованные соединения по TCP и HTTP без TLS.
+    #[serde(default)]
+    pub allow_plaintext: bool,
+
+    /// Ограничение частоты запросов одного пользователя (запросов в секунду)
+    /// и количество запросов, которое можно отправить подряд. 0 - без ограничения.
+    #[serde(default = "default_user_rate_limit")]
+    pub user_rate_limit: f64,
+
+    #[serde(default = "default_user_burst")]
+    pub user_burst: u32,
+
+    /// Ограничение частоты запросов с одного сетевого адреса.
+    /// Не применяется к запросам через Unix сокет.
+    #[serde(default)]
+    pub host_rate_limit: f64,
+
+    #[serde(default)]
+    pub host_burst: u32,
+
+    /// Разрешить пользователю проходить несколько тестов одновременно.
+    /// Иначе начать новый тест можно только после завершения начатого.
+    #[serde(default)]
+    pub allow_parallel_tests: bool,
+
+    /// Что делать с незавершенными попытками при остановке сервера.
+    #[serde(default)]
+    pub shutdown_policy: ShutdownPolicy,
+
+    /// Максимальный размер сообщения клиента в байтах.
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+
+    /// Время ожидания запроса от клиента в секундах.
+    #[serde(default = "default_read_timeout_seconds")]
+    pub read_timeout_seconds: u64,
+
+    /// Количество потоков обработки соединений.
+    #[serde(default = "default_server_threads")]
+    pub server_threads: usize,
+
     #[serde(default)]
     #[serde(rename = "test")]
     pub tests: Vec<TestSettings>,
@@ -66,12 +329,73 @@ pub struct Settings {
     pub log_level: String,
 }
 
+/// Форматы времени открытия и закрытия теста. Последний совпадает
+/// с форматом сериализации chrono.
+const DATETIME_FORMATS: [&str; 3] = [
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S%.f",
+];
+
+/// Время в виде строки "2025-06-01 09:00".
+fn deserialize_datetime<'de, D>(deserializer: D) -> Result<Option<NaiveDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&value, format).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "некорректное время {value}, ожидается формат ГГГГ-ММ-ДД ЧЧ:ММ"
+            ))
+        })
+}
+
+fn default_max_message_size() -> usize {
+    1_048_576
+}
+
+fn default_read_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_server_threads() -> usize {
+    32
+}
+
+fn default_user_rate_limit() -> f64 {
+    5.0
+}
+
+fn default_user_burst() -> u32 {
+    20
+}
+
 impl std::default::Default for Settings {
     fn default() -> Settings {
         Settings {
             tests_directory_path: "tests".to_string(),
             result_path: "results".to_string(),
             server_address: "127.0.0.1:65001".to_string(),
+            socket_path: "".to_string(),
+            http_address: "".to_string(),
+            tls_cert_path: "".to_string(),
+            tls_key_path: "".to_string(),
+            allow_plaintext: false,
+            user_rate_limit: default_user_rate_limit(),
+            user_burst: default_user_burst(),
+            host_rate_limit: 0.0,
+            host_burst: 0,
+            allow_parallel_tests: false,
+            shutdown_policy: ShutdownPolicy::default(),
+            max_message_size: default_max_message_size(),
+            read_timeout_seconds: default_read_timeout_seconds(),
+            server_threads: default_server_threads(),
             tests: vec![],
             log_level: "debug".to_string(),
         }
@@ -1,125 +1,349 @@
 use std::{
+    panic::{self, AssertUnwindSafe},
     sync::{
         mpsc::{self, Receiver, Sender},
-        Arc, Mutex,
+        Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
-use crate::Server;
+use crate::{ratelimit::RateLimiter, Incoming, Server};
 
 use lc_examiner::{
     examiner::Examiner,
-    network::{Command, Request, Response},
+    network::{Command, Request, Response, PROTOCOL_VERSION, SERVER_CAPABILITIES},
 };
 
 enum Tick {
     CollectCompletedTests,
-    Request { request: Request },
+    Request {
+        incoming: Incoming,
+    },
+    /// Остановить сервер.
+    Shutdown,
+    /// Сервер больше не принимает запросов.
+    ServerStopped,
 }
 
-struct ContollerChannel {
+/// Позволяет остановить ExamManager из другого потока,
+/// например из обработчика сигналов.
+#[derive(Clone)]
+pub struct ShutdownHandle {
     tx: Sender<Tick>,
-    rx: Arc<Mutex<Receiver<Response>>>,
 }
 
-struct ExaminerChannel {
-    tx: Sender<Response>,
-    rx: Receiver<Tick>,
+impl ShutdownHandle {
+    /// Начать остановку: перестать принимать соединения, обработать
+    /// принятые запросы и незавершенные попытки, после чего run() вернет управление.
+    pub fn shutdown(&self) {
+        let _ = self.tx.send(Tick::Shutdown);
+    }
 }
 
 pub struct ExamManager {
-    srv: Arc<Mutex<dyn Server + Sync + Send>>,
-    controller_channel: ContollerChannel,
-    examiner_channel: ExaminerChannel,
+    servers: Vec<Arc<dyn Server + Sync + Send>>,
+    tx: Sender<Tick>,
+    rx: Receiver<Tick>,
     examiner: Examiner,
+    /// Количество запросов, обработка которых завершилась аварийно.
+    failures: usize,
+    /// Ограничение частоты запросов по имени пользователя.
+    user_limiter: RateLimiter,
+    /// Ограничение частоты запросов по сетевому адресу.
+    host_limiter: RateLimiter,
+    /// Получена команда остановки.
+    stopping: bool,
+    /// Количество серверов, переставших принимать запросы.
+    stopped_servers: usize,
 }
 
 impl ExamManager {
-    pub fn new(examiner: Examiner, srv: Arc<Mutex<dyn Server + Sync + Send>>) -> ExamManager {
-        let (c_tx, e_rx) = mpsc::channel();
-        let (e_tx, c_rx) = mpsc::channel();
-
-        let examiner_channel = ExaminerChannel { tx: e_tx, rx: e_rx };
-        let controller_channel = ContollerChannel {
-            tx: c_tx,
-            rx: Arc::new(Mutex::new(c_rx)),
-        };
+    pub fn new(examiner: Examiner, servers: Vec<Arc<dyn Server + Sync + Send>>) -> ExamManager {
+        let (tx, rx) = mpsc::channel();
+        let settings = examiner.settings();
 
         ExamManager {
-            srv,
-            controller_channel,
-            examiner_channel,
+            servers,
+            tx,
+            rx,
             examiner,
+            failures: 0,
+            user_limiter: RateLimiter::new(settings.user_rate_limit, settings.user_burst),
+            host_limiter: RateLimiter::new(settings.host_rate_limit, settings.host_burst),
+            stopping: false,
+            stopped_servers: 0,
+        }
+    }
+
+    /// Получить объект для остановки сервера.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            tx: self.tx.clone(),
         }
     }
 
-    /// Запусить сервер
+    /// Запусить сервер. Возвращает управление после остановки.
     pub fn run(&mut self) {
         self.run_collector();
         self.run_mainloop();
         self.examiner_mainloop();
+
+        let policy = self.examiner.settings().shutdown_policy;
+        self.examiner.shutdown(policy);
+        info!("Сервер остановлен.");
     }
 
     /// Пробуждаться каждые 2 секунды
     fn run_collector(&mut self) {
         debug!("Запускаю проверку ограничения времени тестирования.");
-        let tx = self.controller_channel.tx.clone();
+        let tx = self.tx.clone();
         let _ = thread::spawn(move || loop {
             thread::sleep(Duration::from_secs(2));
-            tx.send(Tick::CollectCompletedTests).unwrap();
+            if tx.send(Tick::CollectCompletedTests).is_err() {
+                error!("Очередь задач закрыта, проверка времени тестирования остановлена.");
+                return;
+            }
         });
     }
 
     /// Обработка входящих запросов.
+    /// Соединения обслуживаются серверами параллельно, а запросы передаются
+    /// Экзаменатору по одному через очередь задач.
     fn run_mainloop(&mut self) {
         debug!("Запускаю обработчик запросов.");
-        let srv = Arc::clone(&self.srv);
-        let rx = Arc::clone(&self.controller_channel.rx);
-        let tx = self.controller_channel.tx.clone();
-        let _ = thread::spawn(move || {
-            let mut srv = srv.lock().unwrap();
-            let rx = rx.lock().unwrap();
-            loop {
-                let request = srv.pop_request().unwrap();
-                tx.send(Tick::Request { request }).unwrap();
-                srv.push_response(rx.recv().unwrap());
-            }
-        });
+        for srv in &self.servers {
+            let srv = Arc::clone(srv);
+            let tx = self.tx.clone();
+            let _ = thread::spawn(move || {
+                while let Some(incoming) = srv.pop_request() {
+                    if let Err(mpsc::SendError(tick)) = tx.send(Tick::Request { incoming }) {
+                        error!("Очередь задач закрыта, запрос не может быть обработан.");
+                        if let Tick::Request { incoming } = tick {
+                            let _ = incoming.reply.send(Response::ServerError);
+                        }
+                        return;
+                    }
+                }
+                let _ = tx.send(Tick::ServerStopped);
+            });
+        }
     }
 
     /// Главный цикл обработки задач.
+    /// Завершается, когда все серверы перестали принимать запросы.
     fn examiner_mainloop(&mut self) {
         debug!("Запускаю главный цикл обработки задач.");
-        loop {
-            match self.examiner_channel.rx.recv() {
+        while self.stopped_servers < self.servers.len() {
+            match self.rx.recv() {
                 Ok(tick) => match tick {
-                    Tick::CollectCompletedTests => self.examiner.variant_collector(),
-                    Tick::Request { request } => {
-                        let responce = self.serve_request(request);
-                        self.examiner_channel.tx.send(responce).unwrap();
+                    Tick::CollectCompletedTests => {
+                        let examiner = &mut self.examiner;
+                        let result =
+                            panic::catch_unwind(AssertUnwindSafe(|| examiner.variant_collector()));
+                        if result.is_err() {
+                            self.report_failure("проверки времени тестирования");
+                        }
+                        let now = Instant::now();
+                        self.user_limiter.prune(now);
+                        self.host_limiter.prune(now);
+                    }
+                    Tick::Request { incoming } => {
+                        let responce = if self.allow_request(&incoming) {
+                            self.serve_request_safe(incoming.request)
+                        } else {
+                            Response::TooManyRequests
+                        };
+                        if incoming.reply.send(responce).is_err() {
+                            warn!("Клиент отключился, не дождавшись ответа.");
+                        }
+                    }
+                    Tick::Shutdown => self.stop_servers(),
+                    Tick::ServerStopped => {
+                        self.stopped_servers += 1;
+                        if !self.stopping {
+                            warn!("Сервер перестал принимать запросы.");
+                        }
                     }
                 },
-                Err(err) => error!("Ошибка обработки запроса: {:?}.", err),
+                Err(err) => {
+                    error!("Очередь задач закрыта: {:?}.", err);
+                    break;
+                }
             }
         }
     }
 
+    /// Перестать принимать новые соединения. Уже принятые запросы
+    /// продолжают обрабатываться главным циклом.
+    fn stop_servers(&mut self) {
+        if self.stopping {
+            return;
+        }
+        info!("Останавливаю сервер, обрабатываю принятые запросы.");
+        self.stopping = true;
+        for srv in &self.servers {
+            srv.stop();
+        }
+    }
+
+    /// Проверить ограничения частоты запросов пользователя и адреса клиента.
+    fn allow_request(&mut self, incoming: &Incoming) -> bool {
+        let now = Instant::now();
+        let user = &incoming.request.user;
+        if !self.user_limiter.check(&user_limit_key(incoming), now) {
+            debug!("Превышена частота запросов пользователя {user}.");
+            return false;
+        }
+        if let Some(peer) = &incoming.peer {
+            if !self.host_limiter.check(peer, now) {
+                debug!("Превышена частота запросов с адреса {peer}.");
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Обработать запрос клиента.
+    /// Паника при обработке запроса не останавливает сервер: клиент
+    /// получает ServerError, а сервер продолжает обслуживать остальных.
+    fn serve_request_safe(&mut self, request: Request) -> Response {
+        let description = format!(
+            "запроса {:?} пользователя {}",
+            request.command, request.user
+        );
+        match panic::catch_unwind(AssertUnwindSafe(|| self.serve_request(request))) {
+            Ok(response) => response,
+            Err(_) => {
+                self.report_failure(&description);
+                Response::ServerError
+            }
+        }
+    }
+
+    /// Записать аварийное завершение задачи в журнал.
+    fn report_failure(&mut self, description: &str) {
+        self.failures += 1;
+        error!(
+            "Аварийное завершение {description} (всего ошибок: {}).",
+            self.failures
+        );
+    }
+
     /// Обработать запрос клиента.
     fn serve_request(&mut self, request: Request) -> Response {
         match request.command {
+            Command::Hello {
+                client_version,
+                capabilities,
+            } => greet(&request.user, client_version, &capabilities),
             Command::StartTest => self
                 .examiner
                 .banner_to_start_test(&request.user, &request.test),
-            Command::GetNextQuestion => self.examiner.next_question(&request.user, &request.test),
+            Command::GetNextQuestion => {
+                self.examiner
+                    .next_question(&request.user, &request.test, &request.session)
+            }
             Command::GetAvaliableTests => self.examiner.avaliable_tests(&request.user),
             Command::PutAnswer { answer } => {
                 self.examiner
-                    .put_answer(&request.user, &request.test, &answer)
+                    .put_answer(&request.user, &request.test, &answer, &request.session)
+            }
+            Command::GetQuestion { index } => {
+                self.examiner
+                    .get_question(&request.user, &request.test, index, &request.session)
+            }
+            Command::SetAnswer { index, answer } => self.examiner.set_answer(
+                &request.user,
+                &request.test,
+                index,
+                &answer,
+                &request.session,
+            ),
+            Command::GetAnswersState => {
+                self.examiner
+                    .answers_state(&request.user, &request.test, &request.session)
+            }
+            Command::FinishTest => {
+                self.examiner
+                    .finish_test(&request.user, &request.test, &request.session)
+            }
+            Command::AbandonTest => {
+                self.examiner
+                    .abandon_test(&request.user, &request.test, &request.session)
             }
         }
     }
 }
+
+/// Ответить на приветствие клиента с учетом версии протокола.
+fn greet(username: &str, client_version: u32, capabilities: &[String]) -> Response {
+    if client_version != PROTOCOL_VERSION {
+        warn!(
+            "Пользователь {username} использует клиент с протоколом версии {client_version}, \
+            сервер поддерживает версию {PROTOCOL_VERSION}."
+        );
+        return Response::IncompatibleVersion {
+            server_version: PROTOCOL_VERSION,
+        };
+    }
+
+    debug!("Пользователь {username} подключился, возможности клиента: {capabilities:?}.");
+    Response::Welcome {
+        server_version: PROTOCOL_VERSION,
+        capabilities: SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+    }
+}
+
+/// Ключ ограничения частоты запросов пользователя.
+/// Имя пользователя подтверждено только для локальных соединений (Unix сокет),
+/// по сети его указывает сам клиент. Поэтому для сетевых соединений ключ
+/// включает адрес клиента: иначе любой студент мог бы исчерпать ограничение
+/// однокурсника, отправляя запросы от его имени.
+fn user_limit_key(incoming: &Incoming) -> String {
+    match &incoming.peer {
+        Some(peer) => format!("{peer}/{}", incoming.request.user),
+        None => incoming.request.user.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_limit_key_includes_peer() {
+        let incoming = |peer: Option<&str>| Incoming {
+            request: Request::new("student", "math", Command::GetAvaliableTests),
+            reply: mpsc::channel().0,
+            peer: peer.map(str::to_string),
+        };
+        assert_eq!(user_limit_key(&incoming(None)), "student");
+        assert_eq!(
+            user_limit_key(&incoming(Some("10.0.0.1"))),
+            "10.0.0.1/student"
+        );
+        assert_ne!(
+            user_limit_key(&incoming(Some("10.0.0.1"))),
+            user_limit_key(&incoming(Some("10.0.0.2")))
+        );
+    }
+
+    #[test]
+    fn greet_versions() {
+        let user = "student".to_string();
+        let resp = greet(&user, PROTOCOL_VERSION, &[]);
+        assert!(matches!(resp, Response::Welcome { server_version, .. }
+            if server_version == PROTOCOL_VERSION));
+
+        let resp = greet(&user, PROTOCOL_VERSION + 1, &["unknown".to_string()]);
+        assert_eq!(
+            resp,
+            Response::IncompatibleVersion {
+                server_version: PROTOCOL_VERSION
+            }
+        );
+    }
+}
@@ -1,10 +1,25 @@
+use std::sync::mpsc::Sender;
+
+use lc_examiner::network::{Request, Response};
+
 pub mod exammanager;
+pub mod ratelimit;
+
+/// Запрос клиента вместе с каналом для отправки ответа на него.
+pub struct Incoming {
+    pub request: Request,
+    pub reply: Sender<Response>,
+    /// Сетевой адрес клиента. None для локальных соединений.
+    pub peer: Option<String>,
+}
 
 /// Интерфейс взаимодействия Сервера и Экзаменатора.
 pub trait Server {
-    /// Взять запрос из очереди запроса.
-    fn pop_request(&mut self) -> Option<lc_examiner::network::Request>;
+    /// Взять запрос из очереди запросов.
+    /// Возвращает None, если сервер больше не принимает запросы.
+    fn pop_request(&self) -> Option<Incoming>;
 
-    /// Отправить ответ на запрос.
-    fn push_response(&mut self, response: lc_examiner::network::Response);
+    /// Перестать принимать новые соединения. Запросы уже принятых
+    /// соединений остаются в очереди, после них pop_request возвращает None.
+    fn stop(&self);
 }
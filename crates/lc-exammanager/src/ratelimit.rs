@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Корзина маркеров: каждый запрос забирает маркер, маркеры
+/// восстанавливаются с постоянной скоростью до размера корзины.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Ограничение частоты запросов по ключу (имени пользователя или адресу).
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Запросов в секунду. 0 - без ограничения.
+    rate: f64,
+    /// Количество запросов, которое можно отправить подряд.
+    burst: f64,
+    buckets: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> RateLimiter {
+        RateLimiter {
+            rate: rate.max(0.0),
+            burst: f64::from(burst.max(1)),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Разрешить запрос с ключом key в момент now.
+    pub fn check(&mut self, key: &str, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+
+        let bucket = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: self.burst,
+                updated: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Удалить корзины, которые успели полностью восстановиться.
+    /// Такие корзины не отличаются от новых, а их хранение занимает память.
+    pub fn prune(&mut self, now: Instant) {
+        if self.rate == 0.0 {
+            return;
+        }
+        let refill = Duration::from_secs_f64(self.burst / self.rate);
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < refill);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratelimit_burst_and_refill() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2.0, 3);
+
+        for _ in 0..3 {
+            assert!(limiter.check("student", start));
+        }
+        assert!(!limiter.check("student", start));
+        // Ограничение одного ключа не влияет на другие.
+        assert!(limiter.check("teacher", start));
+
+        // За полсекунды восстанавливается один маркер.
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check("student", later));
+        assert!(!limiter.check("student", later));
+
+        // Маркеры не накапливаются сверх размера корзины.
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.check("student", much_later));
+        }
+        assert!(!limiter.check("student", much_later));
+    }
+
+    #[test]
+    fn ratelimit_disabled() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(0.0, 0);
+        for _ in 0..1000 {
+            assert!(limiter.check("student", now));
+        }
+        assert!(limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn ratelimit_prune() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(1.0, 2);
+        limiter.check("student", start);
+        limiter.check("teacher", start + Duration::from_secs(2));
+
+        limiter.prune(start + Duration::from_secs(3));
+        assert_eq!(limiter.buckets.len(), 1);
+        assert!(limiter.buckets.contains_key("teacher"));
+    }
+}
@@ -1,14 +1,44 @@
 use crate::*;
+use lc_examiner::schema::{AttemptStatus, Mark};
+use lc_examiner::settings::TestSettings;
 use log::error;
 use std::io::Write;
 
+/// Заголовок таблицы результатов.
+const MARKS_HEADER: &str = "testname,username,start_datetime,end_datetime,points,status,\
+max_points,percent,grade,passed,official_points,official_percent\n";
+
 pub struct CsvReporter {
     statistic: Box<dyn Statistic>,
+    /// Настройки тестов, определяющие итоговый результат.
+    tests: Vec<TestSettings>,
 }
 
 impl CsvReporter {
-    pub fn new(statistic: Box<dyn Statistic>) -> CsvReporter {
-        CsvReporter { statistic }
+    pub fn new(statistic: Box<dyn Statistic>, tests: Vec<TestSettings>) -> CsvReporter {
+        CsvReporter { statistic, tests }
+    }
+
+    /// Итоговый результат теста testname по результатам пользователя results.
+    /// Попытки, от которых пользователь отказался, не учитываются.
+    fn official_mark(&self, results: &[MarkRecord], testname: &str) -> Option<Mark> {
+        let marks: Vec<Mark> = results
+            .iter()
+            .filter(|result| result.testname == testname)
+            .filter(|result| result.status != AttemptStatus::Abandoned.as_str())
+            .map(|result| Mark {
+                points: result.mark,
+                max_points: result.max_points,
+                grade: result.grade.clone(),
+                passed: result.passed,
+            })
+            .collect();
+        self.tests
+            .iter()
+            .find(|test| test.caption == testname)
+            .cloned()
+            .unwrap_or_default()
+            .official_mark(&marks)
     }
 }
 
@@ -25,15 +55,29 @@ impl Reporter for CsvReporter {
         };
 
         // Save output file
+        let _ = file.write(MARKS_HEADER.as_bytes());
         for user in &self.statistic.users() {
-            for result in &self.statistic.results(user) {
+            let results = self.statistic.results(user);
+            for result in &results {
+                let official = self.official_mark(&results, &result.testname);
                 let out = format!(
-                    "{},{},{},{},{}\n",
+                    "{},{},{},{},{},{},{},{},{},{},{},{}\n",
                     result.testname,
                     result.username,
                     result.start_datetime.to_string(),
                     result.end_datetime.to_string(),
-                    result.mark
+                    result.mark,
+                    result.status,
+                    result.max_points,
+                    percent(result.mark, result.max_points),
+                    result.grade.as_deref().unwrap_or(""),
+                    passed(result.passed),
+                    official
+                        .as_ref()
+                        .map_or("".to_string(), |mark| mark.points.to_string()),
+                    official
+                        .as_ref()
+                        .map_or("".to_string(), |mark| percent(mark.points, mark.max_points))
                 );
 
                 print!("{}", out);
@@ -54,7 +98,28 @@ impl Reporter for CsvReporter {
         for variant in variant_report {
             println!("## Вариант от {}", variant.start_datetime);
             println!("### Завершен {}", variant.end_datetime);
-            println!("### Оценка {}", variant.mark);
+            if variant.max_points > 0.0 {
+                println!(
+                    "### Оценка {} из {} ({}%)",
+                    variant.mark,
+                    variant.max_points,
+                    percent(variant.mark, variant.max_points)
+                );
+            } else {
+                println!("### Оценка {}", variant.mark);
+            }
+            if let Some(grade) = &variant.grade {
+                println!("### Оценка по шкале теста {grade}");
+            }
+            match variant.passed {
+                Some(true) => println!("### Тест сдан"),
+                Some(false) => println!("### Тест не сдан"),
+                None => (),
+            }
+            println!("### Состояние {}", variant.status);
+            if let (Some(seed), Some(version)) = (variant.seed, &variant.test_version) {
+                println!("### Зерно {seed}, версия теста {version}");
+            }
             println!("### Вопросы: ");
             for question in variant.questions {
                 println!("#### {} ", question.question);
@@ -66,9 +131,13 @@ impl Reporter for CsvReporter {
                     }
 
                     if answer.is_correct {
-                        println!(" _{}_", answer.answer);
+                        print!(" _{}_", answer.answer);
                     } else {
-                        println!(" {}", answer.answer);
+                        print!(" {}", answer.answer);
+                    }
+                    match answer.source_index {
+                        Some(i) => println!(" (ответ {} в файле теста)", i + 1),
+                        None => println!(),
                     }
                 }
                 println!("");
@@ -77,3 +146,132 @@ impl Reporter for CsvReporter {
         println!("");
     }
 }
+
+/// Доля набранных баллов в процентах, пустая строка - максимальный балл неизвестен.
+fn percent(points: f32, max_points: f32) -> String {
+    if max_points > 0.0 {
+        format!("{:.1}", points / max_points * 100.0)
+    } else {
+        "".to_string()
+    }
+}
+
+/// Признак сдачи теста, пустая строка - проходной процент не задан.
+fn passed(passed: Option<bool>) -> &'static str {
+    match passed {
+        Some(true) => "passed",
+        Some(false) => "failed",
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lc_examiner::settings::{GradeSettings, ResultPolicy};
+
+    struct TStatistic {
+        results: Vec<MarkRecord>,
+    }
+
+    impl Statistic for TStatistic {
+        fn users(&mut self) -> Vec<String> {
+            vec!["vlad".to_string()]
+        }
+
+        fn results(&mut self, _username: &String) -> Vec<MarkRecord> {
+            self.results.clone()
+        }
+
+        fn variants(&mut self, _username: &String, _testname: &String) -> Vec<VariantRecord> {
+            vec![]
+        }
+    }
+
+    fn record(mark: f32, grade: Option<&str>, passed: Option<bool>, status: &str) -> MarkRecord {
+        let datetime =
+            chrono::DateTime::parse_from_str("2025-01-27 10:00:00 +03:00", "%Y-%m-%d %H:%M:%S %z")
+                .unwrap();
+        MarkRecord {
+            username: "vlad".to_string(),
+            testname: "math".to_string(),
+            mark,
+            max_points: 3.0,
+            grade: grade.map(String::from),
+            passed,
+            end_datetime: datetime,
+            start_datetime: datetime,
+            status: status.to_string(),
+        }
+    }
+
+    fn math_settings() -> TestSettings {
+        TestSettings {
+            caption: "math".to_string(),
+            grades: vec![GradeSettings {
+                label: "5".to_string(),
+                min_percent: 90.0,
+            }],
+            pass_percent: Some(50.0),
+            official_result: ResultPolicy::Best,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn marks_report() {
+        let statistic = TStatistic {
+            results: vec![
+                record(1.0, None, Some(false), "finished"),
+                record(2.0, None, Some(true), "interrupted"),
+                record(3.0, Some("5"), Some(true), "abandoned"),
+            ],
+        };
+        let mut reporter = CsvReporter::new(Box::new(statistic), vec![math_settings()]);
+        let filename = PathBuf::from("/tmp/lc_marks_report.csv");
+        reporter.marks_report(filename.clone());
+        let report = std::fs::read_to_string(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], MARKS_HEADER.trim_end());
+        let start = "2025-01-27 10:00:00 +03:00";
+        // Попытка, от которой отказались, не выбирается итоговым результатом,
+        // прерванная остановкой сервера - выбирается.
+        assert_eq!(
+            lines[1],
+            format!("math,vlad,{start},{start},1,finished,3,33.3,,failed,2,66.7")
+        );
+        assert_eq!(
+            lines[2],
+            format!("math,vlad,{start},{start},2,interrupted,3,66.7,,passed,2,66.7")
+        );
+        assert_eq!(
+            lines[3],
+            format!("math,vlad,{start},{start},3,abandoned,3,100.0,5,passed,2,66.7")
+        );
+    }
+
+    #[test]
+    fn official_mark_without_attempts() {
+        let reporter = CsvReporter::new(
+            Box::new(TStatistic { results: vec![] }),
+            vec![math_settings()],
+        );
+        let results = vec![record(3.0, Some("5"), Some(true), "abandoned")];
+        assert_eq!(reporter.official_mark(&results, "math"), None);
+        assert_eq!(reporter.official_mark(&[], "math"), None);
+    }
+
+    #[test]
+    fn percent_format() {
+        assert_eq!(percent(1.0, 3.0), "33.3");
+        assert_eq!(percent(2.0, 3.0), "66.7");
+        assert_eq!(percent(3.0, 3.0), "100.0");
+        assert_eq!(percent(1.0, 0.0), "");
+        assert_eq!(passed(Some(true)), "passed");
+        assert_eq!(passed(Some(false)), "failed");
+        assert_eq!(passed(None), "");
+    }
+}
@@ -9,8 +9,15 @@ pub struct MarkRecord {
     pub username: String,
     pub testname: String,
     pub mark: f32,
+    /// Максимально возможный балл варианта, 0 - неизвестен.
+    pub max_points: f32,
+    /// Оценка по шкале теста и признак сдачи теста, если заданы в настройках теста.
+    pub grade: Option<String>,
+    pub passed: Option<bool>,
     pub end_datetime: chrono::DateTime<chrono::FixedOffset>,
     pub start_datetime: chrono::DateTime<chrono::FixedOffset>,
+    /// Чем закончилась попытка: completed, finished, expired, abandoned или interrupted.
+    pub status: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -18,6 +25,8 @@ pub struct AnswerRecord {
     pub answer: String,
     pub is_correct: bool,
     pub is_selected: bool,
+    /// Номер ответа в файле теста, если ответы варианта перемешаны.
+    pub source_index: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -29,9 +38,16 @@ pub struct QuestionRecord {
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct VariantRecord {
     pub mark: f32,
+    pub max_points: f32,
     pub end_datetime: chrono::DateTime<chrono::FixedOffset>,
     pub start_datetime: chrono::DateTime<chrono::FixedOffset>,
     pub questions: Vec<QuestionRecord>,
+    pub status: String,
+    /// Зерно генератора и версия теста, по которым можно воспроизвести вариант.
+    pub seed: Option<u64>,
+    pub test_version: Option<String>,
+    pub grade: Option<String>,
+    pub passed: Option<bool>,
 }
 
 pub trait Reporter {
@@ -0,0 +1,149 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use lc_examiner::{
+    network::{Request, Response},
+    settings::Settings,
+};
+use lc_exammanager::Incoming;
+use log::{debug, error};
+use rustls::ServerConfig;
+
+use crate::{
+    error::{Failures, ProtocolError},
+    frame::{read_message, write_message, DEFAULT_MAX_MESSAGE_SIZE},
+};
+
+/// Параметры обработки соединений.
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    /// Максимальный размер запроса в байтах.
+    pub max_message_size: usize,
+    /// Время ожидания запроса от клиента.
+    pub read_timeout: Duration,
+    /// Количество потоков обработки соединений.
+    pub threads: usize,
+    /// Настройки TLS. Если не указаны, соединения по TCP не шифруются.
+    pub tls: Option<Arc<ServerConfig>>,
+    /// Счетчики ошибок обработки соединений.
+    pub failures: Arc<Failures>,
+}
+
+impl ConnectionOptions {
+    pub fn from_settings(settings: &Settings) -> ConnectionOptions {
+        ConnectionOptions {
+            max_message_size: settings.max_message_size,
+            read_timeout: Duration::from_secs(settings.read_timeout_seconds),
+            threads: settings.server_threads,
+            tls: None,
+            failures: Arc::new(Failures::default()),
+        }
+    }
+
+    /// Записать ошибку соединения в журнал и учесть ее в счетчиках.
+    pub fn report(&self, err: &ProtocolError) {
+        let total = self.failures.record(err);
+        error!("Ошибка обработки соединения: {err} (всего ошибок: {total}).");
+    }
+}
+
+impl std::default::Default for ConnectionOptions {
+    fn default() -> ConnectionOptions {
+        ConnectionOptions {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            read_timeout: Duration::from_secs(10),
+            threads: 32,
+            tls: None,
+            failures: Arc::new(Failures::default()),
+        }
+    }
+}
+
+/// Очередь запросов, прочитанных потоками обработки соединений.
+pub struct RequestQueue {
+    rx: Mutex<Receiver<Incoming>>,
+}
+
+impl RequestQueue {
+    pub fn new() -> (Sender<Incoming>, RequestQueue) {
+        let (tx, rx) = mpsc::channel();
+        (tx, RequestQueue { rx: Mutex::new(rx) })
+    }
+
+    /// Взять следующий запрос. None, если все источники запросов закрыты.
+    pub fn pop(&self) -> Option<Incoming> {
+        // Очередь не должна становиться недоступной из-за паники в другом потоке.
+        let rx = self.rx.lock().unwrap_or_else(|err| err.into_inner());
+        rx.recv().ok()
+    }
+}
+
+/// Обслужить соединение: прочитать запрос, передать его в очередь
+/// и отправить клиенту полученный ответ.
+/// Если пользователь установлен транспортом (username), он заменяет имя из запроса.
+/// peer - сетевой адрес клиента, None для локальных соединений.
+/// Ошибки записываются в журнал, клиент получает ResponseError или ServerError.
+pub fn serve_connection<S: Read + Write>(
+    stream: &mut S,
+    queue: &Sender<Incoming>,
+    options: &ConnectionOptions,
+    username: Option<String>,
+    peer: Option<String>,
+) {
+    let mut request = match read_message::<_, Request>(stream, options.max_message_size) {
+        Ok(request) => request,
+        Err(err) => {
+            options.report(&err);
+            let _ = write_message(stream, &err.response());
+            return;
+        }
+    };
+
+    if let Some(username) = username {
+        if request.user != username {
+            debug!(
+                "Пользователь {username} представился как {}, имя заменено.",
+                request.user
+            );
+        }
+        request.user = username;
+    }
+    debug!("{request:?}");
+
+    let response = dispatch(queue, request, peer).unwrap_or_else(|err| {
+        options.report(&err);
+        err.response()
+    });
+    if let Err(err) = write_message(stream, &response) {
+        options.report(&err);
+    }
+}
+
+/// Сетевой адрес клиента без номера порта.
+pub fn peer_address(stream: &TcpStream) -> Option<String> {
+    stream.peer_addr().ok().map(|addr| addr.ip().to_string())
+}
+
+/// Передать запрос в очередь и дождаться ответа экзаменатора.
+pub fn dispatch(
+    queue: &Sender<Incoming>,
+    request: Request,
+    peer: Option<String>,
+) -> Result<Response, ProtocolError> {
+    let (reply, response) = mpsc::channel();
+    queue
+        .send(Incoming {
+            request,
+            reply,
+            peer,
+        })
+        .map_err(|_| ProtocolError::Closed)?;
+    response.recv().map_err(|_| ProtocolError::Closed)
+}
@@ -0,0 +1,136 @@
+use std::{
+    fmt, io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use lc_examiner::network::Response;
+
+/// Ошибка обмена сообщениями с клиентом.
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// Ошибка чтения или записи, в том числе истечение времени ожидания.
+    Io(io::Error),
+    /// Размер сообщения превышает ограничение.
+    TooLarge { size: usize, limit: usize },
+    /// Сообщение не удалось разобрать.
+    Decode(String),
+    /// Сообщение не удалось сформировать.
+    Encode(String),
+    /// Экзаменатор не принимает запросы или не ответил на запрос.
+    Closed,
+}
+
+impl ProtocolError {
+    /// Ответ клиенту, на запрос которого произошла ошибка.
+    pub fn response(&self) -> Response {
+        match self {
+            ProtocolError::Io(_) | ProtocolError::TooLarge { .. } | ProtocolError::Decode(_) => {
+                Response::ResponseError
+            }
+            ProtocolError::Encode(_) | ProtocolError::Closed => Response::ServerError,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Io(err) => write!(f, "ошибка ввода-вывода: {err}"),
+            ProtocolError::TooLarge { size, limit } => {
+                write!(f, "размер сообщения {size} превышает ограничение {limit}")
+            }
+            ProtocolError::Decode(err) => write!(f, "некорректное сообщение: {err}"),
+            ProtocolError::Encode(err) => write!(f, "не удалось сформировать сообщение: {err}"),
+            ProtocolError::Closed => write!(f, "экзаменатор не принимает запросы"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(err: io::Error) -> ProtocolError {
+        ProtocolError::Io(err)
+    }
+}
+
+impl From<ProtocolError> for io::Error {
+    fn from(err: ProtocolError) -> io::Error {
+        match err {
+            ProtocolError::Io(err) => err,
+            ProtocolError::Closed => io::Error::new(io::ErrorKind::BrokenPipe, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// Счетчики ошибок обработки соединений.
+/// Общие для всех транспортов, использующих одни ConnectionOptions.
+#[derive(Debug, Default)]
+pub struct Failures {
+    io: AtomicUsize,
+    too_large: AtomicUsize,
+    decode: AtomicUsize,
+    server: AtomicUsize,
+}
+
+impl Failures {
+    /// Учесть ошибку. Возвращает общее количество ошибок.
+    pub fn record(&self, err: &ProtocolError) -> usize {
+        let counter = match err {
+            ProtocolError::Io(_) => &self.io,
+            ProtocolError::TooLarge { .. } => &self.too_large,
+            ProtocolError::Decode(_) => &self.decode,
+            ProtocolError::Encode(_) | ProtocolError::Closed => &self.server,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.total()
+    }
+
+    /// Общее количество ошибок.
+    pub fn total(&self) -> usize {
+        self.io.load(Ordering::Relaxed)
+            + self.too_large.load(Ordering::Relaxed)
+            + self.decode.load(Ordering::Relaxed)
+            + self.server.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for Failures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ввод-вывод: {}, превышение размера: {}, некорректные сообщения: {}, ошибки сервера: {}",
+            self.io.load(Ordering::Relaxed),
+            self.too_large.load(Ordering::Relaxed),
+            self.decode.load(Ordering::Relaxed),
+            self.server.load(Ordering::Relaxed)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_counted_by_kind() {
+        let failures = Failures::default();
+        assert_eq!(failures.record(&ProtocolError::Decode("x".to_string())), 1);
+        assert_eq!(
+            failures.record(&ProtocolError::TooLarge { size: 10, limit: 5 }),
+            2
+        );
+        assert_eq!(failures.record(&ProtocolError::Closed), 3);
+        assert_eq!(
+            failures.to_string(),
+            "ввод-вывод: 0, превышение размера: 1, некорректные сообщения: 1, ошибки сервера: 1"
+        );
+
+        assert_eq!(
+            ProtocolError::Decode("x".to_string()).response(),
+            Response::ResponseError
+        );
+        assert_eq!(ProtocolError::Closed.response(), Response::ServerError);
+    }
+}
@@ -0,0 +1,128 @@
+/// Формат сообщений между клиентом и сервером.
+///
+/// Каждое сообщение передается кадром: 4 байта длины сообщения
+/// (big endian), за которыми следует сообщение в формате bincode.
+/// Длина сообщения ограничена, чтобы клиент не мог заставить сервер
+/// выделить произвольный объем памяти.
+use std::io::{Read, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::ProtocolError;
+
+/// Размер заголовка кадра.
+const HEADER_SIZE: usize = 4;
+
+/// Ограничение размера сообщения по умолчанию.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1_048_576;
+
+/// Записать сообщение в поток.
+pub fn write_message<W: Write, T: Serialize>(
+    stream: &mut W,
+    message: &T,
+) -> Result<(), ProtocolError> {
+    let payload =
+        bincode::serialize(message).map_err(|err| ProtocolError::Encode(err.to_string()))?;
+    let len = u32::try_from(payload.len()).map_err(|_| ProtocolError::TooLarge {
+        size: payload.len(),
+        limit: u32::MAX as usize,
+    })?;
+
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(&payload)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Прочитать сообщение из потока.
+/// Сообщения длиннее max_size возвращают ошибку TooLarge,
+/// сообщения, которые не удалось разобрать, - ошибку Decode.
+pub fn read_message<R: Read, T: DeserializeOwned>(
+    stream: &mut R,
+    max_size: usize,
+) -> Result<T, ProtocolError> {
+    let mut header = [0_u8; HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_be_bytes(header) as usize;
+    if len > max_size {
+        return Err(ProtocolError::TooLarge {
+            size: len,
+            limit: max_size,
+        });
+    }
+
+    let mut payload = vec![0_u8; len];
+    stream.read_exact(&mut payload)?;
+    bincode::deserialize(&payload).map_err(|err| ProtocolError::Decode(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lc_examiner::network::{Command, Request, Response};
+    use std::io::{self, Cursor};
+
+    #[test]
+    fn frame_roundtrip() {
+        let request = Request::new("user", "test", Command::StartTest);
+        let mut buffer = vec![];
+        write_message(&mut buffer, &request).unwrap();
+
+        let decoded: Request = read_message(&mut Cursor::new(buffer), 1000).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn frame_split_reads() {
+        /// Поток, отдающий данные по одному байту.
+        struct Slow(Cursor<Vec<u8>>);
+        impl Read for Slow {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(1);
+                self.0.read(&mut buf[..n])
+            }
+        }
+
+        let response = Response::NextQuestion {
+            question: "вопрос ".repeat(1000),
+            answers: vec!["A".to_string(); 100],
+            index: 99,
+            total: 100,
+            remaining_seconds: 3600,
+            question_remaining_seconds: None,
+            multiple_choice: true,
+            answer: None,
+            session: "token".to_string(),
+        };
+        let mut buffer = vec![];
+        write_message(&mut buffer, &response).unwrap();
+
+        let decoded: Response =
+            read_message(&mut Slow(Cursor::new(buffer)), DEFAULT_MAX_MESSAGE_SIZE).unwrap();
+        assert_eq!(decoded, response);
+    }
+
+    #[test]
+    fn frame_oversize() {
+        let request = Request::new("user", "test", Command::StartTest);
+        let mut buffer = vec![];
+        write_message(&mut buffer, &request).unwrap();
+
+        let err = read_message::<_, Request>(&mut Cursor::new(buffer), 10).unwrap_err();
+        assert!(matches!(err, ProtocolError::TooLarge { limit: 10, .. }));
+    }
+
+    #[test]
+    fn frame_garbled() {
+        let mut buffer = 3_u32.to_be_bytes().to_vec();
+        buffer.extend([0xff, 0xff, 0xff]);
+        let err = read_message::<_, Request>(&mut Cursor::new(buffer), 1000).unwrap_err();
+        assert!(matches!(err, ProtocolError::Decode(_)));
+
+        let buffer = 100_u32.to_be_bytes().to_vec();
+        let err = read_message::<_, Request>(&mut Cursor::new(buffer), 1000).unwrap_err();
+        assert!(
+            matches!(err, ProtocolError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof)
+        );
+    }
+}
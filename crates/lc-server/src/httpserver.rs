@@ -0,0 +1,531 @@
+/// HTTP сервер, принимающий запросы в формате JSON.
+///
+/// Каждой команде протокола соответствует адрес `POST /api/<команда>`,
+/// где имя команды записано в snake_case, например `/api/start_test`
+/// или `/api/get_next_question`. Тело запроса - JSON объект с полями
+/// `user`, `test` (необязательное), `session` (необязательное) и полями команды:
+///
+/// POST /api/put_answer
+/// {"user": "student", "test": "linux", "session": "...", "answer": {"answers": [0, 2]}}
+///
+/// Ответ - структура Response в формате JSON, например
+/// {"TestStarted": {"banner": "...", "session": "..."}} или "Ok".
+/// Одно соединение обслуживает один запрос.
+/// Если в настройках указан сертификат, сервер работает по HTTPS.
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+};
+
+use lc_examiner::network::{Command, Request, Response};
+use lc_exammanager::{Incoming, Server};
+use log::{debug, error, info};
+use serde_json::{Map, Value};
+
+use crate::{
+    connection::{dispatch, peer_address, ConnectionOptions, RequestQueue},
+    error::ProtocolError,
+    pool::ThreadPool,
+    tls,
+};
+
+/// Ограничение размера строки запроса и заголовков.
+const MAX_HEADER_SIZE: usize = 8192;
+
+/// Префикс адресов команд.
+const API_PREFIX: &str = "/api/";
+
+/// HTTP сервер. Соединения обслуживаются пулом потоков,
+/// прочитанные запросы складываются в общую очередь.
+pub struct HttpServer {
+    queue: RequestQueue,
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl HttpServer {
+    pub fn new(address: String, options: ConnectionOptions) -> HttpServer {
+        info!("Открываю HTTP порт сервера: {}", address);
+        let listener = TcpListener::bind(address).expect("Не могу открыть соединение");
+        let address = listener
+            .local_addr()
+            .expect("Не могу определить адрес сервера");
+        let (tx, queue) = RequestQueue::new();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let accept_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            let pool = ThreadPool::new(options.threads);
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        error!("Не удалось принять соединение: {err}");
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                let options = options.clone();
+                pool.execute(move || {
+                    if let Err(err) = stream.set_read_timeout(Some(options.read_timeout)) {
+                        error!("Не удалось установить время ожидания запроса: {err}");
+                    }
+                    let peer = peer_address(&stream);
+                    match &options.tls {
+                        Some(config) => match tls::accept(config, stream) {
+                            Ok(mut stream) => {
+                                serve_http(&mut stream, &tx, &options, peer);
+                                tls::close(&mut stream);
+                            }
+                            Err(err) => options.report(&err.into()),
+                        },
+                        None => serve_http(&mut stream, &tx, &options, peer),
+                    }
+                });
+            }
+            // Новые соединения отклоняются, пока завершаются уже принятые.
+            drop(listener);
+        });
+
+        HttpServer {
+            queue,
+            address,
+            stopped,
+        }
+    }
+}
+
+impl Server for HttpServer {
+    /// Взять запрос из очереди запроса.
+    fn pop_request(&self) -> Option<Incoming> {
+        self.queue.pop()
+    }
+
+    /// Закрыть порт сервера.
+    fn stop(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            info!("Закрываю порт сервера: {}", self.address);
+            // Разбудить поток, ожидающий новых соединений.
+            let _ = TcpStream::connect(self.address);
+        }
+    }
+}
+
+/// HTTP запрос клиента.
+#[derive(Debug, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// Ошибка обработки HTTP запроса с кодом ответа.
+#[derive(Debug, PartialEq)]
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new<S: ToString>(status: u16, message: S) -> HttpError {
+        HttpError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Обслужить HTTP соединение.
+/// Ошибки записываются в журнал, клиент получает ответ с кодом ошибки.
+fn serve_http<S: Read + Write>(
+    stream: &mut S,
+    queue: &Sender<Incoming>,
+    options: &ConnectionOptions,
+    peer: Option<String>,
+) {
+    let result = {
+        let mut reader = BufReader::new(&mut *stream);
+        read_http_request(&mut reader, options.max_message_size).and_then(|http| to_request(&http))
+    };
+
+    let (status, response) = match result {
+        Ok(request) => {
+            debug!("{request:?}");
+            let response = dispatch(queue, request, peer).unwrap_or_else(|err| {
+                options.report(&err);
+                err.response()
+            });
+            (status_of(&response), response)
+        }
+        Err(err) => {
+            options.report(&ProtocolError::Decode(err.message));
+            (err.status, Response::ResponseError)
+        }
+    };
+
+    if let Err(err) = write_http_response(stream, status, &response) {
+        options.report(&err.into());
+    }
+}
+
+/// Прочитать HTTP запрос из потока.
+/// Тело запроса длиннее max_size не читается.
+fn read_http_request<R: BufRead>(
+    stream: &mut R,
+    max_size: usize,
+) -> Result<HttpRequest, HttpError> {
+    let mut header_size = 0;
+    let request_line = read_line(stream, &mut header_size)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), path.to_string())
+        }
+        _ => return Err(HttpError::new(400, "Некорректная строка запроса")),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let line = read_line(stream, &mut header_size)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(HttpError::new(400, "Некорректный заголовок"));
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| HttpError::new(400, "Некорректный заголовок Content-Length"))?;
+        } else if name.trim().eq_ignore_ascii_case("transfer-encoding") {
+            return Err(HttpError::new(411, "Необходим заголовок Content-Length"));
+        }
+    }
+
+    if content_length > max_size {
+        return Err(HttpError::new(
+            413,
+            format!("Размер сообщения {content_length} превышает ограничение {max_size}"),
+        ));
+    }
+
+    let mut body = vec![0_u8; content_length];
+    stream
+        .read_exact(&mut body)
+        .map_err(|err| HttpError::new(400, err))?;
+
+    Ok(HttpRequest { method, path, body })
+}
+
+/// Прочитать строку заголовка без символов конца строки.
+fn read_line<R: BufRead>(stream: &mut R, header_size: &mut usize) -> Result<String, HttpError> {
+    let mut line = vec![];
+    let limit = (MAX_HEADER_SIZE - *header_size) as u64;
+    stream
+        .by_ref()
+        .take(limit)
+        .read_until(b'\n', &mut line)
+        .map_err(|err| HttpError::new(400, err))?;
+    *header_size += line.len();
+
+    if !line.ends_with(b"\n") {
+        return Err(HttpError::new(431, "Слишком длинные заголовки запроса"));
+    }
+    let line = String::from_utf8(line).map_err(|err| HttpError::new(400, err))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Преобразовать HTTP запрос в запрос к экзаменатору.
+fn to_request(http: &HttpRequest) -> Result<Request, HttpError> {
+    let Some(command) = http.path.strip_prefix(API_PREFIX) else {
+        return Err(HttpError::new(
+            404,
+            format!("Неизвестный адрес {}", http.path),
+        ));
+    };
+    if http.method != "POST" {
+        return Err(HttpError::new(
+            405,
+            format!("Метод {} не поддерживается", http.method),
+        ));
+    }
+
+    let mut fields: Map<String, Value> = if http.body.is_empty() {
+        Map::new()
+    } else {
+        serde_json::from_slice(&http.body).map_err(|err| HttpError::new(400, err))?
+    };
+
+    let user = match take_string(&mut fields, "user")? {
+        Some(user) => user,
+        None => return Err(HttpError::new(400, "Не указано поле user")),
+    };
+    let test = take_string(&mut fields, "test")?.unwrap_or_default();
+    let session = take_string(&mut fields, "session")?;
+    let command = to_command(command, fields)?;
+
+    Ok(Request {
+        user,
+        test,
+        command,
+        session,
+    })
+}
+
+/// Извлечь строковое поле из тела запроса.
+fn take_string(fields: &mut Map<String, Value>, name: &str) -> Result<Option<String>, HttpError> {
+    match fields.remove(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(HttpError::new(
+            400,
+            format!("Поле {name} должно быть строкой"),
+        )),
+    }
+}
+
+/// Собрать команду по имени из адреса и оставшимся полям тела запроса.
+fn to_command(name: &str, fields: Map<String, Value>) -> Result<Command, HttpError> {
+    let variant = to_camel_case(name);
+    let unknown = || HttpError::new(404, format!("Неизвестная команда {name}"));
+    if variant.is_empty() {
+        return Err(unknown());
+    }
+
+    // Команды без параметров записываются в JSON строкой, с параметрами - объектом.
+    if fields.is_empty() {
+        if let Ok(command) = serde_json::from_value(Value::String(variant.clone())) {
+            return Ok(command);
+        }
+    }
+    let mut object = Map::new();
+    object.insert(variant, Value::Object(fields));
+    serde_json::from_value(Value::Object(object)).map_err(|err| {
+        if err.to_string().starts_with("unknown variant") {
+            unknown()
+        } else {
+            HttpError::new(400, err)
+        }
+    })
+}
+
+/// get_next_question -> GetNextQuestion
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Код HTTP ответа для ответа экзаменатора.
+fn status_of(response: &Response) -> u16 {
+    match response {
+        Response::ResponseError => 400,
+        Response::ServerError => 500,
+        Response::TooManyRequests => 429,
+        _ => 200,
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Записать HTTP ответ с телом в формате JSON.
+fn write_http_response<W: Write>(
+    stream: &mut W,
+    status: u16,
+    response: &Response,
+) -> io::Result<()> {
+    let body = serde_json::to_string(response)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut head = format!(
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reason(status),
+        body.len()
+    );
+    if status == 405 {
+        head.push_str("Allow: POST\r\n");
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lc_examiner::schema::Answer;
+    use ntest::timeout;
+    use std::io::Cursor;
+
+    fn http(method: &str, path: &str, body: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    /// Отправить HTTP запрос и прочитать код и тело ответа.
+    fn send_http(address: &str, raw: &str) -> (u16, Response) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+
+        let (head, body) = answer.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn post(path: &str, body: &str) -> String {
+        format!(
+            "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn http_parse_request() {
+        let raw = post("/api/start_test", r#"{"user": "student"}"#);
+        let request = read_http_request(&mut Cursor::new(raw), 1000).unwrap();
+        assert_eq!(
+            request,
+            http("POST", "/api/start_test", r#"{"user": "student"}"#)
+        );
+
+        let raw = post("/api/start_test", r#"{"user": "student"}"#);
+        let err = read_http_request(&mut Cursor::new(raw), 5).unwrap_err();
+        assert_eq!(err.status, 413);
+
+        let raw = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEADER_SIZE));
+        let err = read_http_request(&mut Cursor::new(raw), 1000).unwrap_err();
+        assert_eq!(err.status, 431);
+
+        let err = read_http_request(&mut Cursor::new("garbage\r\n\r\n"), 1000).unwrap_err();
+        assert_eq!(err.status, 400);
+    }
+
+    #[test]
+    fn http_commands() {
+        let request = to_request(&http(
+            "POST",
+            "/api/get_next_question",
+            r#"{"user": "student", "test": "linux", "session": "token"}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::new("student", "linux", Command::GetNextQuestion).with_session("token")
+        );
+
+        let request = to_request(&http(
+            "POST",
+            "/api/put_answer",
+            r#"{"user": "student", "test": "linux", "answer": {"answers": [0, 2]}}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::new(
+                "student",
+                "linux",
+                Command::PutAnswer {
+                    answer: Answer::new(vec![0, 2])
+                }
+            )
+        );
+
+        let request = to_request(&http(
+            "POST",
+            "/api/hello",
+            r#"{"user": "student", "client_version": 1, "capabilities": []}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            request.command,
+            Command::Hello {
+                client_version: 1,
+                capabilities: vec![]
+            }
+        );
+
+        let err = to_request(&http("POST", "/api/launch_rockets", r#"{"user": "a"}"#));
+        assert_eq!(err.unwrap_err().status, 404);
+        let err = to_request(&http("POST", "/index.html", r#"{"user": "a"}"#));
+        assert_eq!(err.unwrap_err().status, 404);
+        let err = to_request(&http("GET", "/api/start_test", ""));
+        assert_eq!(err.unwrap_err().status, 405);
+        let err = to_request(&http("POST", "/api/start_test", r#"{"test": "linux"}"#));
+        assert_eq!(err.unwrap_err().status, 400);
+        let err = to_request(&http("POST", "/api/put_answer", r#"{"user": "a"}"#));
+        assert_eq!(err.unwrap_err().status, 400);
+        let err = to_request(&http("POST", "/api/start_test", "[1, 2"));
+        assert_eq!(err.unwrap_err().status, 400);
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn http_roundtrip() {
+        let srv = HttpServer::new("127.0.0.1:8895".to_string(), ConnectionOptions::default());
+
+        let client = thread::spawn(|| {
+            let (status, response) = send_http(
+                "127.0.0.1:8895",
+                &post("/api/start_test", r#"{"user": "student", "test": "linux"}"#),
+            );
+            assert_eq!(status, 200);
+            assert_eq!(
+                response,
+                Response::TestStarted {
+                    banner: "Привет".to_string(),
+                    session: "token".to_string()
+                }
+            );
+
+            send_http("127.0.0.1:8895", &post("/api/unknown", r#"{"user": "a"}"#))
+        });
+
+        let reqq = srv.pop_request().unwrap();
+        assert_eq!(
+            reqq.request,
+            Request::new("student", "linux", Command::StartTest)
+        );
+        reqq.reply
+            .send(Response::TestStarted {
+                banner: "Привет".to_string(),
+                session: "token".to_string(),
+            })
+            .unwrap();
+
+        assert_eq!(client.join().unwrap(), (404, Response::ResponseError));
+    }
+}
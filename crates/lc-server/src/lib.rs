@@ -1 +1,8 @@
+pub mod connection;
+pub mod error;
+pub mod frame;
+pub mod httpserver;
+pub mod pool;
 pub mod socketserver;
+pub mod tls;
+pub mod unixserver;
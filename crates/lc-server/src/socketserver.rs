@@ -1,52 +1,96 @@
 use std::{
-    io::{Read, Write},
-    net::{TcpListener, TcpStream},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
 };
 
-use lc_examiner::network;
-use lc_exammanager::Server;
-use log::{debug, info};
+use lc_exammanager::{Incoming, Server};
+use log::{error, info};
 
+use crate::{
+    connection::{peer_address, serve_connection, ConnectionOptions, RequestQueue},
+    pool::ThreadPool,
+    tls,
+};
+
+/// TCP сервер. Соединения обслуживаются пулом потоков,
+/// прочитанные запросы складываются в общую очередь.
 pub struct SocketServer {
-    listener: TcpListener,
-    stream: Option<TcpStream>,
+    queue: RequestQueue,
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
 }
 
 impl SocketServer {
-    pub fn new(address: String) -> SocketServer {
+    pub fn new(address: String, options: ConnectionOptions) -> SocketServer {
         info!("Открываю порт сервера: {}", address);
         let listener = TcpListener::bind(address).expect("Не могу открыть соединение");
+        let address = listener
+            .local_addr()
+            .expect("Не могу определить адрес сервера");
+        let (tx, queue) = RequestQueue::new();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let accept_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            let pool = ThreadPool::new(options.threads);
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        error!("Не удалось принять соединение: {err}");
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                let options = options.clone();
+                pool.execute(move || {
+                    if let Err(err) = stream.set_read_timeout(Some(options.read_timeout)) {
+                        error!("Не удалось установить время ожидания запроса: {err}");
+                    }
+                    let peer = peer_address(&stream);
+                    match &options.tls {
+                        Some(config) => match tls::accept(config, stream) {
+                            Ok(mut stream) => {
+                                serve_connection(&mut stream, &tx, &options, None, peer);
+                                tls::close(&mut stream);
+                            }
+                            Err(err) => options.report(&err.into()),
+                        },
+                        None => serve_connection(&mut stream, &tx, &options, None, peer),
+                    }
+                });
+            }
+            // Новые соединения отклоняются, пока завершаются уже принятые.
+            drop(listener);
+        });
 
         SocketServer {
-            listener,
-            stream: None,
+            queue,
+            address,
+            stopped,
         }
     }
 }
 
 impl Server for SocketServer {
     /// Взять запрос из очереди запроса.
-    fn pop_request(&mut self) -> Option<network::Request> {
-        match self.listener.accept() {
-            Ok((mut stream, _)) => {
-                let mut request = [0 as u8; 5000];
-                let n_bytes = stream.read(&mut request).unwrap();
-                let request =
-                    bincode::deserialize::<network::Request>(&request[0..n_bytes]).unwrap();
-
-                self.stream = Some(stream);
-                debug!("{request:?}");
-                Some(request)
-            }
-            Err(_) => None,
-        }
+    fn pop_request(&self) -> Option<Incoming> {
+        self.queue.pop()
     }
 
-    /// Отправить ответ на запрос.
-    fn push_response(&mut self, response: network::Response) {
-        let response = bincode::serialize(&response).unwrap();
-        if self.stream.is_some() {
-            let _ = self.stream.as_mut().unwrap().write(&response);
+    /// Закрыть порт сервера.
+    fn stop(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            info!("Закрываю порт сервера: {}", self.address);
+            // Разбудить поток, ожидающий новых соединений.
+            let _ = TcpStream::connect(self.address);
         }
     }
 }
@@ -58,35 +102,48 @@ mod tests {
     use lc_examiner::schema::Answer;
     use std::{
         error::Error,
+        io::Write,
+        net::TcpStream,
         thread::{self, sleep},
         time::Duration,
     };
 
     use super::*;
+    use crate::frame::{read_message, write_message, DEFAULT_MAX_MESSAGE_SIZE};
+    use lc_examiner::network;
     use ntest::timeout;
 
     /// Осуществляет связь с сервером.
     fn send_request(request: &Request, listen: String) -> Result<Response, Box<dyn Error>> {
-        let request = bincode::serialize(&request)?;
-        let mut response = [0 as u8; 1_000_000];
-
         let mut stream = TcpStream::connect(listen)?;
-        stream.write(&request)?;
-        let n_bytes = stream.read(&mut response)?;
-
-        let response = bincode::deserialize::<Response>(&response[..n_bytes])?;
+        write_message(&mut stream, request)?;
+        let response = read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE)?;
         match response {
             Response::ServerError => {
                 std::process::exit(1);
             }
 
-            resp => return Ok(resp),
-        };
+            resp => Ok(resp),
+        }
+    }
+
+    fn next_question() -> Response {
+        Response::NextQuestion {
+            question: "oops!\ntext.".to_string(),
+            answers: vec!["A".to_string(), "B".to_string()],
+            index: 0,
+            total: 1,
+            remaining_seconds: 60,
+            question_remaining_seconds: None,
+            multiple_choice: false,
+            answer: None,
+            session: "token".to_string(),
+        }
     }
 
     #[test]
     fn network_single_request() {
-        let mut srv = SocketServer::new("127.0.0.1:8888".to_string());
+        let srv = SocketServer::new("127.0.0.1:8888".to_string(), ConnectionOptions::default());
 
         thread::spawn(|| {
             let req = Request::new("user", "test", network::Command::StartTest);
@@ -97,16 +154,17 @@ mod tests {
         sleep(Duration::from_millis(1));
         let reqq = srv.pop_request().unwrap();
         assert_eq!(
-            reqq,
+            reqq.request,
             Request::new("user", "test", network::Command::StartTest)
         );
-        srv.push_response(Response::Ok);
+        assert_eq!(reqq.peer, Some("127.0.0.1".to_string()));
+        reqq.reply.send(Response::Ok).unwrap();
     }
 
     #[test]
-    #[timeout(100)]
+    #[timeout(1000)]
     fn network_multi_request() {
-        let mut srv = SocketServer::new("127.0.0.1:8889".to_string());
+        let srv = SocketServer::new("127.0.0.1:8889".to_string(), ConnectionOptions::default());
 
         thread::spawn(|| {
             for i in 1..102 {
@@ -118,13 +176,7 @@ mod tests {
                     },
                 );
                 let resp = send_request(&req, "127.0.0.1:8889".to_string()).unwrap();
-                assert_eq!(
-                    resp,
-                    Response::NextQuestion {
-                        question: "oops!\ntext.".to_string(),
-                        answers: vec!["A".to_string(), "B".to_string()]
-                    }
-                );
+                assert_eq!(resp, next_question());
             }
         });
 
@@ -141,16 +193,125 @@ mod tests {
                 },
             );
 
-            assert_eq!(reqq.unwrap(), req);
-            let resp = Response::NextQuestion {
-                question: "oops!\ntext.".to_string(),
-                answers: vec!["A".to_string(), "B".to_string()],
-            };
-            srv.push_response(resp);
+            let incoming = reqq.unwrap();
+            assert_eq!(incoming.request, req);
+            incoming.reply.send(next_question()).unwrap();
             sleep(Duration::from_micros(500));
             reqq = srv.pop_request();
         }
 
         assert_eq!(i, 100);
+
+        // Ответ на последний запрос, иначе клиент получит ServerError.
+        if let Some(incoming) = reqq {
+            incoming.reply.send(next_question()).unwrap();
+        }
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn network_bad_frames() {
+        let options = ConnectionOptions {
+            max_message_size: 100,
+            ..Default::default()
+        };
+        let srv = SocketServer::new("127.0.0.1:8890".to_string(), options);
+
+        let client = thread::spawn(|| {
+            // Сообщение больше допустимого размера.
+            let mut stream = TcpStream::connect("127.0.0.1:8890").unwrap();
+            stream.write_all(&1_000_000_u32.to_be_bytes()).unwrap();
+            let resp = read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE);
+            assert_eq!(resp.unwrap(), Response::ResponseError);
+
+            // Сообщение, которое нельзя разобрать.
+            let mut stream = TcpStream::connect("127.0.0.1:8890").unwrap();
+            stream.write_all(&4_u32.to_be_bytes()).unwrap();
+            stream.write_all(&[0xff; 4]).unwrap();
+            let resp = read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE);
+            assert_eq!(resp.unwrap(), Response::ResponseError);
+
+            let req = Request::new("user", "test", network::Command::StartTest);
+            send_request(&req, "127.0.0.1:8890".to_string()).unwrap()
+        });
+
+        let reqq = srv.pop_request().unwrap();
+        assert_eq!(
+            reqq.request,
+            Request::new("user", "test", network::Command::StartTest)
+        );
+        reqq.reply.send(Response::Ok).unwrap();
+        assert_eq!(client.join().unwrap(), Response::Ok);
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn network_stop_drains_requests() {
+        let srv = SocketServer::new("127.0.0.1:8892".to_string(), ConnectionOptions::default());
+
+        let client = thread::spawn(|| {
+            let req = Request::new("user", "test", network::Command::StartTest);
+            send_request(&req, "127.0.0.1:8892".to_string()).unwrap()
+        });
+        let reqq = srv.pop_request().unwrap();
+
+        // Принятый запрос обслуживается и после остановки сервера.
+        srv.stop();
+        reqq.reply.send(Response::Ok).unwrap();
+        assert_eq!(client.join().unwrap(), Response::Ok);
+
+        // Новые соединения не принимаются, очередь закрывается.
+        assert!(srv.pop_request().is_none());
+        assert!(TcpStream::connect("127.0.0.1:8892").is_err());
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn network_stalled_client() {
+        let options = ConnectionOptions {
+            read_timeout: Duration::from_millis(300),
+            ..Default::default()
+        };
+        let srv = SocketServer::new("127.0.0.1:8891".to_string(), options);
+
+        // Клиент открыл соединение и ничего не отправил.
+        let stalled = thread::spawn(|| {
+            let mut stream = TcpStream::connect("127.0.0.1:8891").unwrap();
+            read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE).unwrap()
+        });
+        sleep(Duration::from_millis(50));
+
+        // Остальные клиенты обслуживаются без ожидания.
+        let clients: Vec<_> = (0..10)
+            .map(|i| {
+                thread::spawn(move || {
+                    let req = Request::new(
+                        format!("user{i}"),
+                        "test".to_string(),
+                        network::Command::StartTest,
+                    );
+                    send_request(&req, "127.0.0.1:8891".to_string()).unwrap()
+                })
+            })
+            .collect();
+
+        for _ in 0..10 {
+            let incoming = srv.pop_request().unwrap();
+            let user = incoming.request.user.clone();
+            incoming
+                .reply
+                .send(Response::TestStarted {
+                    banner: user,
+                    session: "token".to_string(),
+                })
+                .unwrap();
+        }
+        for (i, client) in clients.into_iter().enumerate() {
+            assert!(matches!(client.join().unwrap(),
+                Response::TestStarted { banner, .. } if banner == format!("user{i}")));
+        }
+
+        // Зависшее соединение закрывается по истечении времени ожидания.
+        assert_eq!(stalled.join().unwrap(), Response::ResponseError);
     }
 }
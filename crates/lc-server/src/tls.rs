@@ -0,0 +1,330 @@
+/// Защищенное соединение (TLS) для транспортов поверх TCP.
+///
+/// Сервер использует сертификат и закрытый ключ в формате PEM.
+/// Клиент проверяет сертификат сервера одним из способов:
+/// по набору корневых сертификатов (CA) или по отпечатку SHA-256
+/// сертификата сервера, заданному заранее.
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+};
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, ServerConfig,
+    ServerConnection, SignatureScheme, StreamOwned,
+};
+use sha2::{Digest, Sha256};
+
+/// Способ проверки сертификата сервера клиентом.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trust {
+    /// Путь к файлу корневых сертификатов в формате PEM.
+    CaBundle(String),
+    /// Отпечаток SHA-256 сертификата сервера в шестнадцатеричном виде.
+    Fingerprint(String),
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn tls_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn pem_error(path: &str, err: rustls::pki_types::pem::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Не удалось прочитать {path}: {err}"),
+    )
+}
+
+/// Настройки TLS сервера по путям к сертификату и закрытому ключу.
+pub fn server_config(cert_path: &str, key_path: &str) -> io::Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|err| pem_error(cert_path, err))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| pem_error(cert_path, err))?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Файл {cert_path} не содержит сертификатов"),
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|err| pem_error(key_path, err))?;
+
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(tls_error)?;
+    Ok(Arc::new(config))
+}
+
+/// Настройки TLS клиента.
+pub fn client_config(trust: &Trust) -> io::Result<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?;
+
+    let config = match trust {
+        Trust::CaBundle(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(path).map_err(|err| pem_error(path, err))? {
+                roots
+                    .add(cert.map_err(|err| pem_error(path, err))?)
+                    .map_err(tls_error)?;
+            }
+            if roots.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Файл {path} не содержит сертификатов"),
+                ));
+            }
+            builder.with_root_certificates(roots).with_no_client_auth()
+        }
+        Trust::Fingerprint(fingerprint) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCertificate::new(fingerprint)?))
+            .with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Принять TLS соединение. Рукопожатие выполняется при первом чтении.
+pub fn accept(
+    config: &Arc<ServerConfig>,
+    stream: TcpStream,
+) -> io::Result<StreamOwned<ServerConnection, TcpStream>> {
+    let connection = ServerConnection::new(Arc::clone(config)).map_err(tls_error)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+/// Установить TLS соединение с сервером.
+/// server_name - имя или IP адрес, указанный в сертификате сервера.
+pub fn connect(
+    config: &Arc<ClientConfig>,
+    server_name: &str,
+    stream: TcpStream,
+) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+    let name = ServerName::try_from(server_name.to_string()).map_err(tls_error)?;
+    let connection = ClientConnection::new(Arc::clone(config), name).map_err(tls_error)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+/// Корректно завершить TLS соединение после отправки ответа.
+pub fn close<S: Read + Write>(stream: &mut StreamOwned<ServerConnection, S>) {
+    stream.conn.send_close_notify();
+    let _ = stream.flush();
+}
+
+/// Отпечаток SHA-256 сертификата в виде шестнадцатеричной строки.
+pub fn fingerprint(cert: &[u8]) -> String {
+    Sha256::digest(cert)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Проверка сертификата сервера по известному отпечатку.
+/// Подписи рукопожатия проверяются обычным образом.
+#[derive(Debug)]
+struct PinnedCertificate {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertificate {
+    fn new(fingerprint: &str) -> io::Result<PinnedCertificate> {
+        // Отпечаток может быть записан с двоеточиями и в любом регистре.
+        let fingerprint: String = fingerprint
+            .chars()
+            .filter(|c| *c != ':')
+            .collect::<String>()
+            .to_lowercase();
+        if fingerprint.len() != 64 || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Отпечаток сертификата должен содержать 32 байта SHA-256",
+            ));
+        }
+        Ok(PinnedCertificate {
+            fingerprint,
+            provider: provider(),
+        })
+    }
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if fingerprint(end_entity) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "Отпечаток сертификата сервера не совпадает".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        connection::ConnectionOptions,
+        frame::{read_message, write_message, DEFAULT_MAX_MESSAGE_SIZE},
+        socketserver::SocketServer,
+    };
+    use lc_examiner::network::{Command, Request, Response};
+    use lc_exammanager::Server;
+    use ntest::timeout;
+    use std::thread;
+
+    /// Самоподписанный сертификат для localhost, сохраненный во временный каталог.
+    struct SelfSigned {
+        cert_path: String,
+        key_path: String,
+        fingerprint: String,
+    }
+
+    impl SelfSigned {
+        fn new(name: &str) -> SelfSigned {
+            let dir = std::env::temp_dir().join(format!("lc_tls_{name}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+            let cert_path = dir.join("cert.pem");
+            let key_path = dir.join("key.pem");
+            std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+            std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+
+            SelfSigned {
+                cert_path: cert_path.to_str().unwrap().to_string(),
+                key_path: key_path.to_str().unwrap().to_string(),
+                fingerprint: fingerprint(cert.cert.der()),
+            }
+        }
+    }
+
+    fn tls_server(address: &str, cert: &SelfSigned) -> SocketServer {
+        let options = ConnectionOptions {
+            tls: Some(server_config(&cert.cert_path, &cert.key_path).unwrap()),
+            ..Default::default()
+        };
+        SocketServer::new(address.to_string(), options)
+    }
+
+    fn send_tls(address: &str, trust: &Trust) -> io::Result<Response> {
+        let config = client_config(trust)?;
+        let mut stream = connect(&config, "localhost", TcpStream::connect(address)?)?;
+        write_message(
+            &mut stream,
+            &Request::new("user", "test", Command::StartTest),
+        )?;
+        Ok(read_message(&mut stream, DEFAULT_MAX_MESSAGE_SIZE)?)
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn tls_ca_bundle() {
+        let cert = SelfSigned::new("ca_bundle");
+        let srv = tls_server("127.0.0.1:8896", &cert);
+
+        let trust = Trust::CaBundle(cert.cert_path.clone());
+        let client = thread::spawn(move || send_tls("127.0.0.1:8896", &trust).unwrap());
+
+        let reqq = srv.pop_request().unwrap();
+        assert_eq!(
+            reqq.request,
+            Request::new("user", "test", Command::StartTest)
+        );
+        reqq.reply.send(Response::Ok).unwrap();
+        assert_eq!(client.join().unwrap(), Response::Ok);
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn tls_pinned_fingerprint() {
+        let cert = SelfSigned::new("fingerprint");
+        let srv = tls_server("127.0.0.1:8897", &cert);
+
+        // Неверный отпечаток: соединение не устанавливается, запрос не доходит до сервера.
+        let wrong = Trust::Fingerprint("00".repeat(32));
+        assert!(send_tls("127.0.0.1:8897", &wrong).is_err());
+
+        let trust = Trust::Fingerprint(cert.fingerprint.to_uppercase());
+        let client = thread::spawn(move || send_tls("127.0.0.1:8897", &trust).unwrap());
+
+        let reqq = srv.pop_request().unwrap();
+        reqq.reply.send(Response::Ok).unwrap();
+        assert_eq!(client.join().unwrap(), Response::Ok);
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn tls_rejects_plaintext() {
+        let cert = SelfSigned::new("plaintext");
+        let _srv = tls_server("127.0.0.1:8898", &cert);
+
+        let mut stream = TcpStream::connect("127.0.0.1:8898").unwrap();
+        write_message(
+            &mut stream,
+            &Request::new("user", "test", Command::StartTest),
+        )
+        .unwrap();
+        assert!(read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE).is_err());
+    }
+
+    #[test]
+    fn tls_bad_settings() {
+        assert!(server_config("/nonexistent/cert.pem", "/nonexistent/key.pem").is_err());
+        assert!(client_config(&Trust::CaBundle("/nonexistent/ca.pem".to_string())).is_err());
+        assert!(client_config(&Trust::Fingerprint("abc".to_string())).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    ffi::CStr,
+    io, mem,
+    os::unix::{
+        fs::PermissionsExt,
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use lc_examiner::network::Response;
+use lc_exammanager::{Incoming, Server};
+use log::{error, info};
+
+use crate::{
+    connection::{serve_connection, ConnectionOptions, RequestQueue},
+    frame::write_message,
+    pool::ThreadPool,
+};
+
+/// Сервер на Unix сокете.
+/// Имя пользователя определяется ядром по учетным данным процесса клиента
+/// (SO_PEERCRED), поле Request.user от клиента игнорируется.
+pub struct UnixSocketServer {
+    path: PathBuf,
+    queue: RequestQueue,
+    stopped: Arc<AtomicBool>,
+}
+
+impl UnixSocketServer {
+    pub fn new<P: Into<PathBuf>>(path: P, options: ConnectionOptions) -> UnixSocketServer {
+        let path = path.into();
+        info!("Открываю сокет сервера: {}", path.display());
+
+        // Сокет мог остаться после аварийного завершения сервера.
+        if path.exists() {
+            let _ = std::fs::remove_file(&path);
+        }
+        let listener = UnixListener::bind(&path).expect("Не могу открыть сокет");
+        // Подключаться к сокету могут все пользователи, личность проверяется ядром.
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666));
+        let (tx, queue) = RequestQueue::new();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let accept_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            let pool = ThreadPool::new(options.threads);
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        error!("Не удалось принять соединение: {err}");
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                let options = options.clone();
+                pool.execute(move || {
+                    if let Err(err) = stream.set_read_timeout(Some(options.read_timeout)) {
+                        error!("Не удалось установить время ожидания запроса: {err}");
+                    }
+                    match peer_username(&stream) {
+                        Ok(username) => {
+                            serve_connection(&mut stream, &tx, &options, Some(username), None)
+                        }
+                        Err(err) => {
+                            error!("Не удалось определить пользователя сокета: {err}");
+                            options.report(&err.into());
+                            let _ = write_message(&mut stream, &Response::NotAllowedUser);
+                        }
+                    }
+                });
+            }
+            // Новые соединения отклоняются, пока завершаются уже принятые.
+            drop(listener);
+        });
+
+        UnixSocketServer {
+            path,
+            queue,
+            stopped,
+        }
+    }
+}
+
+impl Server for UnixSocketServer {
+    /// Взять запрос из очереди запроса.
+    fn pop_request(&self) -> Option<Incoming> {
+        self.queue.pop()
+    }
+
+    /// Закрыть сокет сервера.
+    fn stop(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            info!("Закрываю сокет сервера: {}", self.path.display());
+            // Разбудить поток, ожидающий новых соединений.
+            let _ = UnixStream::connect(&self.path);
+        }
+    }
+}
+
+impl Drop for UnixSocketServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Имя пользователя процесса на другой стороне сокета.
+fn peer_username(stream: &UnixStream) -> io::Result<String> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    username_by_uid(cred.uid)
+}
+
+/// Найти имя пользователя по uid в базе учетных записей.
+fn username_by_uid(uid: libc::uid_t) -> io::Result<String> {
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::from_raw_os_error(rc));
+    }
+    if result.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Пользователь с uid {uid} не найден"),
+        ));
+    }
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use lc_examiner::network::{Command, Request, Response};
+    use std::{error::Error, thread};
+
+    use super::*;
+    use crate::frame::{read_message, DEFAULT_MAX_MESSAGE_SIZE};
+    use ntest::timeout;
+
+    fn send_request(request: &Request, path: &str) -> Result<Response, Box<dyn Error>> {
+        let mut stream = UnixStream::connect(path)?;
+        write_message(&mut stream, request)?;
+        Ok(read_message(&mut stream, DEFAULT_MAX_MESSAGE_SIZE)?)
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn unix_peer_username() {
+        let path = "/tmp/lc_unix_peer_username.sock";
+        let srv = UnixSocketServer::new(path, ConnectionOptions::default());
+
+        let client = thread::spawn(move || {
+            let req = Request::new("impostor", "test", Command::StartTest);
+            send_request(&req, path).unwrap()
+        });
+
+        let reqq = srv.pop_request().unwrap();
+        let username = username_by_uid(unsafe { libc::getuid() }).unwrap();
+        assert_eq!(
+            reqq.request,
+            Request::new(username.as_str(), "test", Command::StartTest)
+        );
+        assert_eq!(reqq.peer, None);
+        reqq.reply.send(Response::Ok).unwrap();
+        assert_eq!(client.join().unwrap(), Response::Ok);
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn unix_socket_removed_on_drop() {
+        let path = "/tmp/lc_unix_socket_removed.sock";
+        {
+            let _srv = UnixSocketServer::new(path, ConnectionOptions::default());
+            assert!(std::path::Path::new(path).exists());
+        }
+        assert!(!std::path::Path::new(path).exists());
+    }
+}
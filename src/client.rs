@@ -3,23 +3,32 @@
 use std::error::Error;
 use std::io::prelude::*;
 use std::net::TcpStream;
-
-use rustyline::DefaultEditor;
-use whoami;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use lc_examiner::{
-    network::{Command, Marks, Request, Response},
-    schema::Answer,
+    network::{Command, Marks, Request, Response, PROTOCOL_VERSION},
+    schema::{Answer, Availability, Mark, WindowState},
 };
+use lc_server::frame::{read_message, write_message, DEFAULT_MAX_MESSAGE_SIZE};
+use lc_server::tls::{self, Trust};
+use rustyline::DefaultEditor;
 
 /// Парсит аргументы и запускает соответствующее действие.
 fn main() {
     match std::env::args().nth(1) {
         Some(v) => match v.as_str() {
-            "-l" | "--list" => print_avaliable_tests(),
+            "-l" | "--list" => {
+                check_version();
+                print_avaliable_tests()
+            }
             "-h" | "--help" => print_help(),
             "-V" | "--version" => println!("learned-cat 0.2.0"),
-            test => start_test(test.to_string()),
+            test => {
+                check_version();
+                start_test(test.to_string())
+            }
         },
         None => print_help(),
     }
@@ -45,30 +54,67 @@ fn print_help() {
     );
 }
 
+/// Проверяет совместимость версий клиента и сервера.
+fn check_version() {
+    let request = Request::new(
+        whoami::username(),
+        "".to_string(),
+        Command::Hello {
+            client_version: PROTOCOL_VERSION,
+            capabilities: vec!["sessions".to_string()],
+        },
+    );
+
+    match send_request(&request) {
+        Ok(Response::Welcome { .. }) => (),
+        Ok(Response::IncompatibleVersion { server_version }) => {
+            eprintln!(
+                "Версия протокола клиента ({PROTOCOL_VERSION}) не совпадает с версией сервера ({server_version})."
+            );
+            eprintln!("Пожалуйста, обновите learned-cat или обратитесь к администратору.");
+            std::process::exit(1);
+        }
+        Ok(_) => {
+            eprintln!("Сервер не поддерживает эту версию клиента.");
+            eprintln!("Пожалуйста, обратитесь к администратору.");
+            std::process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("Ошибка связи с сервером. Пожалуйста, повторите попытку позже.");
+            std::process::exit(1);
+        }
+    }
+}
+
 /// Обслуживает процесс тестирования.
 fn start_test(test_name: String) {
     let request = Request::new(whoami::username(), test_name.clone(), Command::StartTest);
 
     match send_request(&request) {
         Ok(response) => match response {
-            Response::TestStarted { banner } => {
+            Response::TestStarted { banner, session } => {
                 println!("{banner}");
                 println!("Вы готовы начать тестирование? (y/n)");
                 if ask_yes() {
-                    run_test(test_name, None);
+                    run_test(test_name, session);
                 }
             }
 
-            Response::NextQuestion { question, answers } => run_test(
-                test_name,
-                Some(Response::NextQuestion { question, answers }),
-            ),
-
             Response::End { marks } => {
                 print!("Тест завершён. Ваш результат: ");
                 print_marks(marks);
             }
 
+            Response::OtherTestInProgress { testname } => print_test_in_progress(testname),
+
+            Response::AttemptCooldown { remaining_seconds } => {
+                print_attempt_cooldown(remaining_seconds)
+            }
+
+            Response::TestUnavailable { availability } => {
+                eprintln!("Тест {}.", format_availability(&availability))
+            }
+
             _ => print_help(),
         },
         Err(_) => eprintln!("Ошибка связи с сервером. Пожалуйста, повторите попытку позже."),
@@ -95,121 +141,379 @@ fn ask_yes() -> bool {
     }
 }
 
-fn run_test(test_name: String, next_question: Option<Response>) {
-    let next_question_request = Request::new(
-        whoami::username(),
-        test_name.clone(),
-        Command::GetNextQuestion,
-    );
+/// Проводит тестирование: студент переходит между вопросами,
+/// отвечает на них в любом порядке и завершает тест.
+fn run_test(test_name: String, session: String) {
+    let request = |command| {
+        Request::new(whoami::username(), test_name.clone(), command).with_session(&session)
+    };
 
-    let mut next_question = next_question;
-    loop {
-        let response = match next_question {
-            Some(q) => {
-                next_question = None;
-                Ok(q)
-            }
-            None => send_request(&next_question_request),
-        };
+    // Продолжить с первого неотвеченного вопроса.
+    let mut index = match send_request(&request(Command::GetAnswersState)) {
+        Ok(Response::AnswersState {
+            answered, closed, ..
+        }) => first_unanswered(&completed(&answered, &closed), 0).unwrap_or(0),
+        response => return print_attempt_end(response),
+    };
+    print_navigation_help();
 
-        match response {
-            Ok(Response::NextQuestion { question, answers }) => {
-                let answers = ask_question(question, answers);
-                let put_answer_request = Request::new(
-                    whoami::username(),
-                    test_name.clone(),
-                    Command::PutAnswer {
-                        answer: Answer::new(answers),
-                    },
-                );
-
-                match send_request(&put_answer_request) {
-                    Ok(Response::End { marks }) => {
-                        print!("Тест завершён. Ваш результат: ");
-                        print_marks(marks);
-                        break;
+    loop {
+        let (question, answers, answer, progress) =
+            match send_request(&request(Command::GetQuestion { index })) {
+                Ok(Response::NextQuestion {
+                    question,
+                    answers,
+                    index,
+                    total,
+                    remaining_seconds,
+                    question_remaining_seconds,
+                    multiple_choice,
+                    answer,
+                    ..
+                }) => {
+                    let progress = Progress {
+                        index,
+                        total,
+                        multiple_choice,
+                        deadline: Instant::now() + Duration::from_secs(remaining_seconds),
+                        question_deadline: question_remaining_seconds
+                            .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+                    };
+                    (question, answers, answer, progress)
+                }
+                response => return print_attempt_end(response),
+            };
+
+        match ask_question(question, answers, answer, &progress) {
+            Input::Answer(answer) => {
+                let command = Command::SetAnswer {
+                    index,
+                    answer: Answer::new(answer),
+                };
+                match send_request(&request(command)) {
+                    Ok(Response::Ok) => (),
+                    Ok(Response::QuestionTimeOver) => {
+                        println!("Время ответа на вопрос истекло, ответ не сохранён.")
                     }
-
-                    Ok(Response::NextQuestion { question, answers }) => {
-                        next_question = Some(Response::NextQuestion { question, answers })
+                    response => return print_attempt_end(response),
+                }
+                let done = match send_request(&request(Command::GetAnswersState)) {
+                    Ok(Response::AnswersState {
+                        answered, closed, ..
+                    }) => completed(&answered, &closed),
+                    response => return print_attempt_end(response),
+                };
+                match first_unanswered(&done, index + 1) {
+                    Some(next) => index = next,
+                    None => {
+                        println!("Вы ответили на все вопросы. Завершить тест? (y/n)");
+                        if ask_yes() {
+                            return print_attempt_end(send_request(&request(Command::FinishTest)));
+                        }
+                        index = 0;
                     }
-
-                    _ => (),
                 }
             }
-
-            Ok(Response::End { marks }) => {
-                print!("Тест завершён. Ваш результат: ");
-                print_marks(marks);
-                break;
+            Input::Next => index = (index + 1) % progress.total,
+            Input::Previous => index = (index + progress.total - 1) % progress.total,
+            Input::Goto(next) => index = next,
+            Input::State => match send_request(&request(Command::GetAnswersState)) {
+                Ok(Response::AnswersState {
+                    answered, closed, ..
+                }) => print_answers_state(&answered, &closed),
+                response => return print_attempt_end(response),
+            },
+            Input::Finish => {
+                let answered = match send_request(&request(Command::GetAnswersState)) {
+                    Ok(Response::AnswersState { answered, .. }) => answered,
+                    response => return print_attempt_end(response),
+                };
+                let unanswered = answered.iter().filter(|a| !**a).count();
+                if unanswered > 0 {
+                    println!("Вопросов без ответа: {unanswered}, они будут оценены нулем.");
+                }
+                println!("Завершить тест? (y/n)");
+                if ask_yes() {
+                    return print_attempt_end(send_request(&request(Command::FinishTest)));
+                }
+            }
+            Input::Abandon => {
+                println!("Отказаться от попытки? Данные ответы не будут учтены. (y/n)");
+                if !ask_yes() {
+                    continue;
+                }
+                match send_request(&request(Command::AbandonTest)) {
+                    Ok(Response::End { .. }) => println!("Попытка не учтена."),
+                    Ok(Response::AbandonNotAllowed) => {
+                        println!("Настройки теста не разрешают отказаться от попытки.");
+                        continue;
+                    }
+                    response => print_attempt_end(response),
+                }
+                return;
             }
+        }
+    }
+}
 
-            _ => (),
+/// Вопросы, с которыми студент закончил: отвеченные и вопросы, время ответа на которые истекло.
+fn completed(answered: &[bool], closed: &[bool]) -> Vec<bool> {
+    answered
+        .iter()
+        .zip(closed)
+        .map(|(answered, closed)| *answered || *closed)
+        .collect()
+}
+
+/// Номер первого неотвеченного вопроса, начиная с вопроса start (по кругу).
+fn first_unanswered(answered: &[bool], start: usize) -> Option<usize> {
+    (0..answered.len())
+        .map(|i| (start + i) % answered.len())
+        .find(|&i| !answered[i])
+}
+
+/// Сообщить о завершении попытки или об ошибке.
+fn print_attempt_end(response: Result<Response, Box<dyn Error>>) {
+    match response {
+        Ok(Response::End { marks }) => {
+            print!("Тест завершён. Ваш результат: ");
+            print_marks(marks);
         }
+        Ok(Response::InvalidSession) => print_invalid_session(),
+        Ok(Response::OtherTestInProgress { testname }) => print_test_in_progress(testname),
+        Ok(Response::NotAllowedUser) => eprintln!("Тест вам недоступен."),
+        Ok(Response::AbandonNotAllowed) => {
+            eprintln!("Настройки теста не разрешают отказаться от попытки.")
+        }
+        _ => eprintln!("Ошибка связи с сервером. Пожалуйста, повторите попытку позже."),
+    }
+}
+
+/// Подсказка по переходам между вопросами.
+fn print_navigation_help() {
+    println!();
+    println!("Введите номера ответов, чтобы ответить на вопрос, или - чтобы отменить ответ.");
+    println!("Переходы: > или пустая строка - следующий вопрос, < - предыдущий, #N - вопрос N,");
+    println!("          ? - перечень вопросов без ответа, ! - завершить тест,");
+    println!("          x - отказаться от попытки, если это разрешено.");
+}
+
+/// Вывести отвеченные и неотвеченные вопросы.
+fn print_answers_state(answered: &[bool], closed: &[bool]) {
+    let numbers = |filter: &dyn Fn(usize) -> bool| -> Vec<String> {
+        (0..answered.len())
+            .filter(|&i| filter(i))
+            .map(|i| (i + 1).to_string())
+            .collect()
+    };
+    let unanswered = numbers(&|i| !answered[i] && !closed[i]);
+    let expired = numbers(&|i| !answered[i] && closed[i]);
+    println!(
+        "Отвечено вопросов: {} из {}.",
+        answered.len() - unanswered.len() - expired.len(),
+        answered.len()
+    );
+    if !unanswered.is_empty() {
+        println!("Без ответа: {}", unanswered.join(" "));
+    }
+    if !expired.is_empty() {
+        println!("Время ответа истекло: {}", expired.join(" "));
     }
 }
 
+/// Сообщение о недействительной сессии тестирования.
+fn print_invalid_session() {
+    eprintln!("Сессия тестирования недействительна. Запустите тест заново.");
+}
+
+/// Сообщение о незавершенном тесте.
+fn print_test_in_progress(testname: String) {
+    eprintln!("Сначала завершите тест {testname}: learned-cat {testname}");
+}
+
+/// Сообщение о времени, когда станет доступна следующая попытка.
+fn print_attempt_cooldown(remaining_seconds: u64) {
+    let available = chrono::Local::now() + chrono::Duration::seconds(remaining_seconds as i64);
+    eprintln!(
+        "Следующая попытка будет доступна в {} (через {} мин.).",
+        available.format("%H:%M"),
+        remaining_seconds.div_ceil(60)
+    );
+}
+
 /// Вывод результата
 fn print_marks(marks: Marks) {
     match marks {
-        Marks::Marks { marks } => {
-            for mark in marks {
-                print!("{mark:.2} ");
+        Marks::Marks { marks, official } => {
+            for mark in &marks {
+                print_mark(mark);
             }
-            println!("");
+            // Итог показывается, только если попыток несколько.
+            if let Some(official) = official.filter(|_| marks.len() > 1) {
+                print!(" Итог: ");
+                print_mark(&official);
+            }
+            println!();
         }
         Marks::Done => {
             println!("Тест завершён.");
         }
         Marks::Empty => {
-            println!("");
+            println!();
+        }
+    }
+}
+
+/// Вывод результата одной попытки: баллы, процент, оценка и признак сдачи.
+fn print_mark(mark: &Mark) {
+    match mark.percent() {
+        Some(percent) => {
+            print!("{:.2}/{:.2} ({percent:.0}%", mark.points, mark.max_points);
+            if let Some(grade) = &mark.grade {
+                print!(", {grade}");
+            }
+            match mark.passed {
+                Some(true) => print!(", сдан"),
+                Some(false) => print!(", не сдан"),
+                None => (),
+            }
+            print!(") ");
+        }
+        None => print!("{:.2} ", mark.points),
+    }
+}
+
+/// Положение вопроса в тесте, время окончания теста и ответа на вопрос.
+struct Progress {
+    index: usize,
+    total: usize,
+    multiple_choice: bool,
+    deadline: Instant,
+    question_deadline: Option<Instant>,
+}
+
+impl Progress {
+    /// Оставшееся время в формате ММ:СС.
+    fn countdown(&self) -> String {
+        let mut countdown = format_remaining(self.deadline);
+        if let Some(question_deadline) = self.question_deadline {
+            countdown += &format!(", на вопрос {}", format_remaining(question_deadline));
         }
+        countdown
+    }
+
+    /// Истекло ли время ответа на вопрос.
+    fn question_closed(&self) -> bool {
+        self.question_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
     }
 }
 
+/// Время до deadline в формате ММ:СС.
+fn format_remaining(deadline: Instant) -> String {
+    let seconds = deadline.saturating_duration_since(Instant::now()).as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Действие студента на вопросе.
+enum Input {
+    /// Номера выбранных ответов, начиная с нуля. Пустой список отменяет ответ.
+    Answer(Vec<usize>),
+    Next,
+    Previous,
+    /// Перейти к вопросу с номером, начиная с нуля.
+    Goto(usize),
+    State,
+    Finish,
+    Abandon,
+}
+
 /// Задает вопрос
-fn ask_question(question: String, answers: Vec<String>) -> Vec<usize> {
-    println!("");
-    println!("        ***");
+fn ask_question(
+    question: String,
+    answers: Vec<String>,
+    answer: Option<Answer>,
+    progress: &Progress,
+) -> Input {
+    println!();
+    println!(
+        "        *** Вопрос {} из {}, осталось {} ***",
+        progress.index + 1,
+        progress.total,
+        progress.countdown()
+    );
     println!("{question}");
-    for i in 0..answers.len() {
-        println!("{}) {}", i + 1, answers[i]);
+    for (i, answer) in answers.iter().enumerate() {
+        println!("{}) {}", i + 1, answer);
+    }
+    if progress.multiple_choice {
+        println!("Выберите все правильные ответы и введите их номера через пробел.");
+    } else {
+        println!("Выберите один правильный ответ.");
+    }
+    if progress.question_closed() {
+        println!("Время ответа на вопрос истекло.");
+    }
+    if let Some(answer) = answer {
+        let numbers: Vec<String> = answer
+            .as_array()
+            .iter()
+            .map(|a| (a + 1).to_string())
+            .collect();
+        println!("Ваш ответ: {}", numbers.join(" "));
     }
 
     'ask: loop {
-        let answer = ask_string();
+        // Оставшееся время обновляется при каждом запросе ввода.
+        let answer = ask_string(&format!("[{}] >>> ", progress.countdown()));
+        match answer.trim() {
+            "" | ">" => return Input::Next,
+            "<" => return Input::Previous,
+            "?" => return Input::State,
+            "!" => return Input::Finish,
+            "x" | "х" => return Input::Abandon,
+            goto if goto.starts_with('#') => match goto[1..].trim().parse::<usize>() {
+                Ok(n) if n > 0 && n <= progress.total => return Input::Goto(n - 1),
+                _ => {
+                    println!("В тесте нет такого вопроса.");
+                    continue;
+                }
+            },
+            "-" => return Input::Answer(vec![]),
+            _ => (),
+        }
+
         let mut answer: Vec<usize> = answer
             .replace(",", " ")
-            .replace("  ", " ")
-            .trim()
-            .split(" ")
-            .map(|x| match x.parse::<usize>() {
-                Ok(v) => v,
-                _ => 100000000,
-            })
+            .split_whitespace()
+            .map(|x| x.parse::<usize>().unwrap_or(100000000))
             .collect();
 
-        for i in 0..answer.len() {
-            if answer[i] as usize <= answers.len() && answer[i] > 0 {
-                answer[i] -= 1;
+        for a in answer.iter_mut() {
+            if *a <= answers.len() && *a > 0 {
+                *a -= 1;
             } else {
                 println!("Пожалуйста, введите номера правильных ответов через пробел.");
                 continue 'ask;
             }
         }
+        if !progress.multiple_choice && answer.len() > 1 {
+            println!("Пожалуйста, введите номер одного правильного ответа.");
+            continue;
+        }
 
-        return answer;
+        return Input::Answer(answer);
     }
 }
 
-fn ask_string() -> String {
+fn ask_string(prompt: &str) -> String {
     loop {
         let mut rl = match DefaultEditor::new() {
             Ok(v) => v,
             _ => continue,
         };
 
-        match rl.readline(">>> ") {
+        match rl.readline(prompt) {
             Ok(v) => return v,
             Err(rustyline::error::ReadlineError::Eof) => std::process::exit(0),
             _ => continue,
@@ -237,30 +541,55 @@ fn print_avaliable_tests() {
     }
 }
 
-/// Вывод таблицы ключ-значение
-fn print_table(values: Vec<(String, Marks)>) {
+/// Вывод таблицы тестов: название, доступность и результат
+fn print_table(values: Vec<(String, Marks, Availability)>) {
     let mut max_first = 0;
+    let mut max_second = "Доступ".chars().count();
     for first in &values {
         max_first = std::cmp::max(max_first, first.0.len());
+        max_second = std::cmp::max(max_second, format_availability(&first.2).chars().count());
     }
 
-    println!("{:>max_first$}   Ваш результат", "Тест");
+    println!(
+        "{:>max_first$}   {:<max_second$} Ваш результат",
+        "Тест", "Доступ"
+    );
     for first in &values {
-        print!("{:>max_first$} ", first.0);
+        print!(
+            "{:>max_first$} {:<max_second$} ",
+            first.0,
+            format_availability(&first.2)
+        );
         print_marks(first.1.clone());
     }
 }
 
+/// Состояние окна доступности теста, например "открыт до 2025-06-01 12:00".
+fn format_availability(availability: &Availability) -> String {
+    let format = |datetime: chrono::NaiveDateTime| datetime.format("%Y-%m-%d %H:%M").to_string();
+    match (
+        availability.state,
+        availability.open_at,
+        availability.close_at,
+    ) {
+        (WindowState::Upcoming, Some(open_at), _) => format!("откроется {}", format(open_at)),
+        (WindowState::Closed, _, Some(close_at)) => format!("закрыт с {}", format(close_at)),
+        (WindowState::Open, _, Some(close_at)) => format!("открыт до {}", format(close_at)),
+        (WindowState::Upcoming, _, _) => "еще не открыт".to_string(),
+        (WindowState::Closed, _, _) => "закрыт".to_string(),
+        (WindowState::Open, _, _) => "открыт".to_string(),
+    }
+}
+
 /// Осуществляет связь с сервером.
+/// Если сервер просит снизить частоту запросов, запрос повторяется после паузы.
 fn send_request(request: &Request) -> Result<Response, Box<dyn Error>> {
-    let request = bincode::serialize(&request)?;
-    let mut response = [0 as u8; 1_000_000];
-
-    let mut stream = TcpStream::connect(get_server_address())?;
-    stream.write(&request)?;
-    let n_bytes = stream.read(&mut response)?;
+    let mut response = connect_and_exchange(request)?;
+    while response == Response::TooManyRequests {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        response = connect_and_exchange(request)?;
+    }
 
-    let response = bincode::deserialize::<Response>(&response[..n_bytes])?;
     match response {
         Response::ServerError => {
             println!("Произошли технические шоколадки :(");
@@ -268,8 +597,92 @@ fn send_request(request: &Request) -> Result<Response, Box<dyn Error>> {
             std::process::exit(1);
         }
 
-        resp => return Ok(resp),
+        resp => Ok(resp),
+    }
+}
+
+/// Подключается к серверу и выполняет один запрос.
+fn connect_and_exchange(request: &Request) -> Result<Response, Box<dyn Error>> {
+    let response = match get_server_socket() {
+        Some(path) => exchange(UnixStream::connect(path)?, request)?,
+        None => {
+            let address = get_server_address();
+            let stream = TcpStream::connect(&address)?;
+            match get_server_trust() {
+                Some(trust) => {
+                    let config = match tls::client_config(&trust) {
+                        Ok(config) => config,
+                        Err(err) => {
+                            eprintln!("Ошибка настройки защищенного соединения: {err}");
+                            std::process::exit(1);
+                        }
+                    };
+                    let stream = tls::connect(&config, &get_server_name(&address), stream)?;
+                    exchange(stream, request)?
+                }
+                None => exchange(stream, request)?,
+            }
+        }
+    };
+    Ok(response)
+}
+
+/// Отправляет запрос в поток и читает ответ.
+fn exchange<S: Read + Write>(mut stream: S, request: &Request) -> std::io::Result<Response> {
+    write_message(&mut stream, request)?;
+    Ok(read_message(&mut stream, DEFAULT_MAX_MESSAGE_SIZE)?)
+}
+
+/// Путь к Unix сокету сервера, если сервер доступен через сокет.
+fn get_server_socket() -> Option<PathBuf> {
+    if let Ok(val) = std::env::var("SERVER_SOCKET") {
+        return Some(PathBuf::from(val));
+    }
+    if std::env::var("SERVER_ADDRESS").is_ok() {
+        return None;
+    }
+    let path = PathBuf::from("/run/learned-cat/learned-cat.sock");
+    if path.exists() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Способ проверки сертификата сервера.
+/// None, если соединение без шифрования явно разрешено.
+fn get_server_trust() -> Option<Trust> {
+    if let Ok(path) = std::env::var("SERVER_TLS_CA") {
+        return Some(Trust::CaBundle(path));
+    }
+    if let Ok(fingerprint) = std::env::var("SERVER_TLS_FINGERPRINT") {
+        return Some(Trust::Fingerprint(fingerprint));
+    }
+    if let Ok(val) = std::env::var("SERVER_PLAINTEXT") {
+        if matches!(val.as_str(), "1" | "yes" | "true") {
+            return None;
+        }
+    }
+
+    eprintln!("Не настроено защищенное соединение с сервером.");
+    eprintln!("Укажите сертификат центра сертификации (SERVER_TLS_CA) или отпечаток");
+    eprintln!("сертификата сервера (SERVER_TLS_FINGERPRINT). Для соединения без шифрования");
+    eprintln!("укажите SERVER_PLAINTEXT=1.");
+    std::process::exit(1);
+}
+
+/// Имя сервера для проверки сертификата.
+fn get_server_name(address: &str) -> String {
+    if let Ok(val) = std::env::var("SERVER_TLS_NAME") {
+        return val;
+    }
+    let host = match address.rsplit_once(':') {
+        Some((host, _)) => host,
+        None => address,
     };
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string()
 }
 
 fn get_server_address() -> String {
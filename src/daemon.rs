@@ -1,20 +1,28 @@
 use clap::arg;
 use lc_database::TestDatabase;
-use lc_examiner::examiner::Examiner;
-use lc_exammanager::exammanager::ExamManager;
+use lc_examiner::examiner::{variant_questions, Examiner};
+use lc_exammanager::exammanager::{ExamManager, ShutdownHandle};
+use lc_exammanager::Server;
 use lc_reporter::Reporter;
+use lc_server::connection::ConnectionOptions;
+use lc_server::httpserver::HttpServer;
 use lc_server::socketserver::SocketServer;
+use lc_server::tls;
+use lc_server::unixserver::UnixSocketServer;
 use log4rs::append::{console::ConsoleAppender, file::FileAppender};
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
 use std::env::set_current_dir;
 use std::error::Error;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
 use lc_config::TomlConfig;
+use lc_examiner::settings::Settings;
 use lc_examiner::Config;
 use lc_reporter::Statistic;
 
@@ -28,14 +36,19 @@ fn main() -> Result<(), Box<dyn Error>> {
         },
         Some(("export-marks", args)) => {
             let output_filename = PathBuf::from(args.get_one::<String>("filename")
-                             .or(Some(&"output.csv".to_string()))
-                             .unwrap());
+                             .unwrap_or(&"output.csv".to_string()));
             export_marks(root_path, output_filename)?
         },
         Some(("export-variants", args)) => {
             let username = args.get_one::<String>("user").unwrap();
             let testname = args.get_one::<String>("test").unwrap();
-            export_variants(root_path, &username, &testname)?
+            export_variants(root_path, username, testname)?
+        },
+        Some(("regenerate-variant", args)) => {
+            let testname = args.get_one::<String>("test").unwrap();
+            let seed = args.get_one::<String>("seed").unwrap();
+            let version = args.get_one::<String>("version").unwrap();
+            regenerate_variant(root_path, testname, seed, version)?
         },
         Some((&_, _)) => error!("Неизвестная команда."),
         None => error!("Необходимо указать команду. Для просмотра доступных команд используйте переметр --help"),
@@ -47,13 +60,16 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn export_marks(root_path: PathBuf, output_filename: PathBuf) -> Result<(), Box<dyn Error>> {
     // Подключаемся к настройкам и базе данных
     let config = TomlConfig::new(&root_path).unwrap();
-    let tests_path = Path::new(&root_path).join(&config.settings().result_path.clone());
+    let tests_path = Path::new(&root_path).join(config.settings().result_path.clone());
     let statistic: Box<dyn Statistic> =
         Box::new(TestDatabase::new(tests_path.to_str().unwrap().to_string()));
 
     // Запускаем генератор отчетов
     let mut reporter: Box<dyn Reporter> =
-        Box::new(lc_reporter::csv_reporter::CsvReporter::new(statistic));
+        Box::new(lc_reporter::csv_reporter::CsvReporter::new(
+            statistic,
+            config.settings().tests.clone(),
+        ));
 
     reporter.marks_report(output_filename);
 
@@ -68,19 +84,60 @@ fn export_variants(
 ) -> Result<(), Box<dyn Error>> {
     // Подключаемся к настройкам и базе данных
     let config = TomlConfig::new(&root_path).unwrap();
-    let tests_path = Path::new(&root_path).join(&config.settings().result_path.clone());
+    let tests_path = Path::new(&root_path).join(config.settings().result_path.clone());
     let statistic: Box<dyn Statistic> =
         Box::new(TestDatabase::new(tests_path.to_str().unwrap().to_string()));
 
     // Запускаем генератор отчетов
     let mut reporter: Box<dyn Reporter> =
-        Box::new(lc_reporter::csv_reporter::CsvReporter::new(statistic));
+        Box::new(lc_reporter::csv_reporter::CsvReporter::new(
+            statistic,
+            config.settings().tests.clone(),
+        ));
 
     reporter.variants_report(username, testname);
 
     Ok(())
 }
 
+/// Вывести вопросы варианта, созданного с зерном seed по версии теста version.
+fn regenerate_variant(
+    root_path: PathBuf,
+    testname: &String,
+    seed: &str,
+    version: &str,
+) -> Result<(), Box<dyn Error>> {
+    let config = TomlConfig::new(&root_path)?;
+    let seed: u64 = seed
+        .parse()
+        .map_err(|_| format!("Некорректное зерно генератора: {seed}"))?;
+    let Some(current_version) = config.test_version(testname) else {
+        return Err(format!("Тест {testname} не найден.").into());
+    };
+    // Другая версия теста или его настроек дает другой набор вопросов при том же зерне.
+    if current_version != version {
+        return Err(format!(
+            "Версия теста {testname} изменилась: {current_version}, вариант создан по версии {version}."
+        )
+        .into());
+    }
+
+    println!("# Вариант теста {testname}, зерно {seed}, версия {version}\n");
+    for question in variant_questions(&config, testname, seed) {
+        println!("#### {}", question.question);
+        let correct = question.correct_answer.as_array();
+        for (i, answer) in question.answers.iter().enumerate() {
+            if correct.contains(&i) {
+                println!("+ {answer}");
+            } else {
+                println!("- {answer}");
+            }
+        }
+        println!();
+    }
+    Ok(())
+}
+
 /// Запуск сервера.
 fn start_server(path: PathBuf) -> Result<(), Box<dyn Error>> {
     set_daemon_dir(&path).expect("Невозможно перейти в директорию с файлами сервера.");
@@ -89,23 +146,98 @@ fn start_server(path: PathBuf) -> Result<(), Box<dyn Error>> {
     start_logger(config.settings().log_level.clone());
 
     debug!("Открываю базу данных.");
-    let tests_path = Path::new(&path).join(&config.settings().result_path.clone());
+    let tests_path = Path::new(&path).join(config.settings().result_path.clone());
     let database = TestDatabase::new(tests_path.to_str().unwrap().to_string());
 
     debug!("Запуска сервер.");
-    let server = SocketServer::new(config.settings().server_address.clone());
+    let servers = start_transports(&config.settings())?;
+    if servers.is_empty() {
+        error!("Не указан ни один адрес сервера (server_address, socket_path, http_address).");
+        return Err(Box::new(std::fmt::Error));
+    }
 
     debug!("Подготавливаю правила обработки тестов.");
     let examiner = Examiner::new(Box::new(config), Box::new(database));
 
     debug!("Подготовка всех систем.");
-    let mut controller = ExamManager::new(examiner, Arc::new(Mutex::new(server)));
+    let mut controller = ExamManager::new(examiner, servers);
+
+    handle_signals(controller.shutdown_handle())?;
 
     debug!("Запуск.");
     controller.run();
+    info!("Работа сервера завершена.");
     Ok(())
 }
 
+/// Остановить сервер по SIGTERM или SIGINT.
+/// Первый сигнал запускает корректную остановку, повторный завершает процесс сразу.
+fn handle_signals(handle: ShutdownHandle) -> Result<(), Box<dyn Error>> {
+    let mut signals = Signals::new([SIGTERM, SIGINT])?;
+    std::thread::spawn(move || {
+        let mut received = false;
+        for signal in signals.forever() {
+            if received {
+                warn!("Повторный сигнал {signal}, завершаю работу без сохранения.");
+                std::process::exit(1);
+            }
+            received = true;
+            info!("Получен сигнал {signal}, останавливаю сервер.");
+            handle.shutdown();
+        }
+    });
+    Ok(())
+}
+
+/// Открыть способы подключения клиентов, указанные в настройках.
+/// Unix сокет заменяет TCP порт, HTTP сервер запускается дополнительно к ним.
+fn start_transports(
+    settings: &Settings,
+) -> Result<Vec<Arc<dyn Server + Sync + Send>>, Box<dyn Error>> {
+    let mut options = ConnectionOptions::from_settings(settings);
+    let uses_tcp = (settings.socket_path.is_empty() && !settings.server_address.is_empty())
+        || !settings.http_address.is_empty();
+
+    if !settings.tls_cert_path.is_empty() || !settings.tls_key_path.is_empty() {
+        match tls::server_config(&settings.tls_cert_path, &settings.tls_key_path) {
+            Ok(config) => options.tls = Some(config),
+            Err(err) => {
+                error!("Не удалось загрузить сертификат сервера: {err}");
+                return Err(Box::new(err));
+            }
+        }
+    } else if uses_tcp && !settings.allow_plaintext {
+        error!("Не указан сертификат сервера (tls_cert_path, tls_key_path).");
+        error!("Для работы без шифрования укажите allow_plaintext = true.");
+        return Err(Box::new(std::fmt::Error));
+    } else if uses_tcp {
+        warn!("Соединения по сети не шифруются (allow_plaintext = true).");
+    }
+
+    let mut servers: Vec<Arc<dyn Server + Sync + Send>> = vec![];
+
+    if !settings.socket_path.is_empty() {
+        servers.push(Arc::new(UnixSocketServer::new(
+            settings.socket_path.clone(),
+            options.clone(),
+        )));
+    } else if !settings.server_address.is_empty() {
+        servers.push(Arc::new(SocketServer::new(
+            settings.server_address.clone(),
+            options.clone(),
+        )));
+    }
+
+    if !settings.http_address.is_empty() {
+        servers.push(Arc::new(HttpServer::new(
+            settings.http_address.clone(),
+            options,
+        )));
+    }
+
+    Ok(servers)
+}
+
 fn str2log_level(log_level: String) -> log::LevelFilter {
     if log_level.as_str() == "debug" {
         log::LevelFilter::Debug
@@ -166,7 +298,7 @@ fn arguments() -> clap::ArgMatches {
         .subcommand(
             clap::Command::new("export-marks")
                 .short_flag('m')
-                .about("экспортировать результаты тестирования в виде csv таблицы следующего формата: <test>,<student>,<time_begin>,<time_end>,<result>")
+                .about("экспортировать результаты тестирования в виде csv таблицы следующего формата: <test>,<student>,<time_begin>,<time_end>,<result>,<status>,<max_result>,<percent>,<grade>,<passed|failed>,<official_result>,<official_percent>")
                 .arg(arg!([filename]).required(true)),
         )
 
@@ -177,6 +309,14 @@ fn arguments() -> clap::ArgMatches {
                         .arg(arg!([user]).required(true))
                         .arg(arg!([test]).required(true))
                 )
+
+        .subcommand(
+            clap::Command::new("regenerate-variant")
+                .about("восстановить вопросы варианта теста по зерну генератора и версии теста из отчета export-variants")
+                .arg(arg!([test]).required(true))
+                .arg(arg!([seed]).required(true))
+                .arg(arg!([version]).required(true))
+        )
         .get_matches()
 }
 
@@ -5,12 +5,11 @@ use std::net::TcpStream;
 /// Отправляет запросы на сервер и предоставляет пользовательский интерфейс.
 use std::{error::Error, thread};
 
-use whoami;
-
 use lc_examiner::{
     network::{Command, Request, Response},
     schema::Answer,
 };
+use lc_server::frame::{read_message, write_message, DEFAULT_MAX_MESSAGE_SIZE};
 
 /// Парсит аргументы и запускает соответствующее действие.
 fn main() {
@@ -33,7 +32,7 @@ fn test_avaliable_tests() {
                 Response::AvaliableTests { tests } => {}
                 _ => eprintln!("Ошибка чтения списка тестов."),
             },
-            Err(err) => eprintln!("Ошибка связи с сервером: {}", err.to_string()),
+            Err(err) => eprintln!("Ошибка связи с сервером: {}", err),
         }
     }
 
@@ -51,9 +50,10 @@ fn test_avaliable_tests() {
 
 fn test_tests() {
     let start = chrono::Local::now();
-    let mut n = 100;
-    for i in 0..n {
-        let k = i.clone();
+    let tests_number = 100;
+    let mut n = tests_number;
+    for i in 0..tests_number {
+        let k = i;
         //thread::spawn(move || {
         //    let mut n = 0;
         start_test("algo".to_string(), &mut n, k % 250);
@@ -79,24 +79,25 @@ fn start_test(test_name: String, n: &mut usize, k: usize) {
 
     match send_request(&request, n) {
         Ok(response) => match response {
-            Response::TestStarted { banner } => {
-                run_test(test_name, None, n, k);
+            Response::TestStarted { banner, session } => {
+                run_test(test_name, session, n, k);
             }
 
             _ => eprintln!("Сервер не ответил"),
         },
-        Err(err) => eprintln!("Ошибка связи с сервером: {}", err.to_string()),
+        Err(err) => eprintln!("Ошибка связи с сервером: {}", err),
     }
 }
 
-fn run_test(test_name: String, next_question: Option<Response>, n: &mut usize, k: usize) {
+fn run_test(test_name: String, session: String, n: &mut usize, k: usize) {
     let next_question_request = Request::new(
         format!("user{k}"),
         test_name.clone(),
         Command::GetNextQuestion,
-    );
+    )
+    .with_session(&session);
 
-    let mut next_question = next_question;
+    let mut next_question = None;
     loop {
         let response = match next_question {
             Some(q) => {
@@ -107,7 +108,7 @@ fn run_test(test_name: String, next_question: Option<Response>, n: &mut usize, k
         };
 
         match response {
-            Ok(Response::NextQuestion { question, answers }) => {
+            Ok(Response::NextQuestion { .. }) => {
                 let answers = vec![0];
                 let put_answer_request = Request::new(
                     format!("user{k}"),
@@ -115,16 +116,15 @@ fn run_test(test_name: String, next_question: Option<Response>, n: &mut usize, k
                     Command::PutAnswer {
                         answer: Answer::new(answers),
                     },
-                );
+                )
+                .with_session(&session);
 
                 match send_request(&put_answer_request, n) {
                     Ok(Response::End { marks }) => {
                         break;
                     }
 
-                    Ok(Response::NextQuestion { question, answers }) => {
-                        next_question = Some(Response::NextQuestion { question, answers })
-                    }
+                    Ok(response @ Response::NextQuestion { .. }) => next_question = Some(response),
 
                     _ => (),
                 }
@@ -142,14 +142,10 @@ fn run_test(test_name: String, next_question: Option<Response>, n: &mut usize, k
 /// Осуществляет связь с сервером.
 fn send_request(request: &Request, n: &mut usize) -> Result<Response, Box<dyn Error>> {
     *n += 1;
-    let request = bincode::serialize(&request)?;
-    let mut response = [0 as u8; 1_000_000];
-
     let mut stream = TcpStream::connect(get_server_address())?;
-    stream.write(&request)?;
-    let n_bytes = stream.read(&mut response)?;
+    write_message(&mut stream, request)?;
 
-    let response = bincode::deserialize::<Response>(&response[..n_bytes])?;
+    let response = read_message::<_, Response>(&mut stream, DEFAULT_MAX_MESSAGE_SIZE)?;
     match response {
         Response::ServerError => {
             println!("Произошли технические шоколадки :(");
@@ -157,8 +153,8 @@ fn send_request(request: &Request, n: &mut usize) -> Result<Response, Box<dyn Er
             std::process::exit(1);
         }
 
-        resp => return Ok(resp),
-    };
+        resp => Ok(resp),
+    }
 }
 
 fn get_server_address() -> String {